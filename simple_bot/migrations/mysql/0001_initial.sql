@@ -0,0 +1,23 @@
+create table if not exists ubi_user (
+    id varchar(64) not null,
+    name varchar(32) not null,
+    ts timestamp not null default CURRENT_TIMESTAMP,
+    primary key (id, name)
+);
+create table if not exists key_word (
+    id bigint not null primary key auto_increment,
+    group_id bigint not null,
+    regex varchar(128),
+    reply varchar(128),
+    chance integer not null default 100
+);
+create table if not exists disabled_plugin (
+    group_id bigint not null,
+    plugin varchar(64) not null,
+    primary key (group_id, plugin)
+);
+create table if not exists blacklist (
+    group_id bigint not null,
+    user_id bigint not null,
+    primary key (group_id, user_id)
+);
@@ -0,0 +1 @@
+alter table key_word add column enabled integer not null default 1;
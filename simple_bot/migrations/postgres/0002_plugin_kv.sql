@@ -0,0 +1,6 @@
+create table if not exists plugin_kv (
+    plugin varchar(64) not null,
+    name varchar(128) not null,
+    value text not null,
+    primary key (plugin, name)
+);
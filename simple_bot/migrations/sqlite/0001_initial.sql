@@ -11,3 +11,13 @@ create table if not exists key_word (
     reply varchar(128),
     chance integer not null default 100
 );
+create table if not exists disabled_plugin (
+    group_id integer not null,
+    plugin varchar(64) not null,
+    primary key (group_id, plugin)
+);
+create table if not exists blacklist (
+    group_id integer not null,
+    user_id integer not null,
+    primary key (group_id, user_id)
+);
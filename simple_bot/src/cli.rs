@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    pub static ref ARGS: Args = Args::parse();
+}
+
+#[derive(Parser)]
+#[command(version, about = "基于 proc_qq 的 QQ 机器人")]
+pub struct Args {
+    /// 配置文件路径
+    #[arg(short, long, env = "SIMPLEBOT_CONFIG", default_value = "config.yml")]
+    pub config: PathBuf,
+
+    /// 数据目录，相对路径的 sqlite 数据库放在这里
+    #[arg(short, long, env = "SIMPLEBOT_DATA_DIR", default_value = ".")]
+    pub data_dir: PathBuf,
+
+    /// 日志级别: trace, debug, info, warn, error
+    #[arg(short, long, env = "SIMPLEBOT_LOG_LEVEL", default_value = "debug")]
+    pub log_level: tracing::Level,
+
+    /// 不连接数据库，所有数据保存在内存中，退出后丢失，用于试用插件
+    #[arg(long, env = "SIMPLEBOT_MEMORY")]
+    pub memory: bool,
+}
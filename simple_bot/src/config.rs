@@ -1,23 +1,41 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
+use std::path::Path;
+
+use serde_yaml::{Mapping, Value};
+use tracing::{info, warn};
+
+use crate::cli;
+use crate::plugin;
+use crate::plugin::limit::RateLimit;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Config {
     pub account: AccountConfig,
-    pub saucenao: SauceNaoConfig,
-    pub divtrack: DivTrackConfig,
     pub database: HashMap<String, String>,
+    #[serde(default)]
+    pub owners: Vec<i64>,
+    #[serde(default)]
+    pub dispatch: DispatchConfig,
+    // overrides the limits declared on actions, keyed by "<plugin id>::<action name>"
+    #[serde(default)]
+    pub rate_limits: HashMap<String, RateLimit>,
+    // the sections handed to each plugin through its `PluginContext`, keyed by plugin id
+    #[serde(default)]
+    pub plugins: BTreeMap<String, Value>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             account: AccountConfig::default(),
-            saucenao: SauceNaoConfig::default(),
-            divtrack: DivTrackConfig::default(),
             database: [("sqlite".to_owned(), "sqlite://sqlite.db".to_owned())]
                 .into_iter()
                 .collect(),
+            owners: Vec::new(),
+            dispatch: DispatchConfig::default(),
+            rate_limits: HashMap::new(),
+            plugins: BTreeMap::new(),
         }
     }
 }
@@ -40,50 +58,476 @@ impl Default for AccountConfig {
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
-pub struct DivTrackConfig {
-    pub ubi_username: String,
-    pub ubi_password: String,
-    pub chrome_port: i16,
+#[serde(default)]
+pub struct DispatchConfig {
+    // how long an event waits for a plugin before moving on to the next one, in milliseconds;
+    // a consuming command plugin takes a message as soon as one of its patterns matches
+    pub wait_millis: u64,
+    // how long a plugin may work on a single event, in seconds, 0 means no limit
+    pub timeout_secs: u64,
+    // timeout_secs of individual plugins, keyed by plugin id
+    pub timeouts: HashMap<String, u64>,
+    // how long the plugins may take to shut down on exit, in seconds
+    pub shutdown_secs: u64,
 }
 
-impl Default for DivTrackConfig {
+impl Default for DispatchConfig {
     fn default() -> Self {
-        DivTrackConfig {
-            ubi_username: String::default(),
-            ubi_password: String::default(),
-            chrome_port: 9515,
+        DispatchConfig {
+            wait_millis: 3000,
+            timeout_secs: 60,
+            timeouts: HashMap::new(),
+            shutdown_secs: 10,
         }
     }
 }
 
+impl DispatchConfig {
+    pub fn get_timeout(&self, plugin: &str) -> Option<std::time::Duration> {
+        let secs = self
+            .timeouts
+            .get(plugin)
+            .copied()
+            .unwrap_or(self.timeout_secs);
+        if secs == 0 {
+            None
+        } else {
+            Some(std::time::Duration::from_secs(secs))
+        }
+    }
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct SauceNaoConfig {
-    pub api_key: String,
+    pub fn get_shutdown_deadline(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.shutdown_secs)
+    }
 }
 
-impl Default for SauceNaoConfig {
-    fn default() -> Self {
-        SauceNaoConfig {
-            api_key: String::default(),
+// environment variables like SIMPLEBOT_DISPATCH__TIMEOUT_SECS override the file, `__`
+// separates the levels
+const ENV_PREFIX: &str = "SIMPLEBOT_";
+// read by `cli::Args` instead
+const CLI_ENV: [&str; 3] = ["SIMPLEBOT_CONFIG", "SIMPLEBOT_DATA_DIR", "SIMPLEBOT_LOG_LEVEL"];
+// `ubi_password_file: /run/secrets/ubi` sets `ubi_password` to the content of the file
+const FILE_SUFFIX: &str = "_file";
+const LEGACY_SECTIONS: [LegacySection; 2] = [
+    LegacySection {
+        name: "saucenao",
+        plugin: "Search",
+        renames: &[("api_key", "saucenao_api_key")],
+    },
+    LegacySection {
+        name: "divtrack",
+        plugin: "Div",
+        renames: &[],
+    },
+];
+
+// a section of config.yml from before plugins had their own, moved into `plugins.<id>`
+// when the file is read
+struct LegacySection {
+    name: &'static str,
+    plugin: &'static str,
+    // (old field, new field)
+    renames: &'static [(&'static str, &'static str)],
+}
+
+pub fn path() -> &'static Path {
+    &cli::ARGS.config
+}
+
+pub fn read_config() -> anyhow::Result<Config> {
+    let path = path();
+    if !path.exists() {
+        let default_config = default_config()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+        serde_yaml::to_writer(File::create(path)?, &default_config)?;
+        return Err(anyhow::anyhow!(
+            "配置文件 {} 不存在，自动创建默认配置，请更改配置后重启机器人！",
+            path.display()
+        ));
+    }
+    let (file, changed) = read_file()?;
+    let config = parse(file.clone())?;
+    // only at startup and once the config is known to be valid, rewriting drops the comments
+    // of the file, and on a reload the write would wake the watcher again
+    if changed {
+        serde_yaml::to_writer(File::create(path)?, &file)?;
+        info!("已将迁移后的配置写回 {}", path.display());
     }
+    Ok(config)
 }
 
-pub fn read_config() -> anyhow::Result<Config> {
-    let file_name = "config.yml";
-    match File::open(file_name) {
-        Ok(file) => {
-            let config = serde_yaml::from_reader(file)?;
-            Ok(config)
+// for a reload, the file is never written
+pub fn load_config() -> anyhow::Result<Config> {
+    let (file, _) = read_file()?;
+    parse(file)
+}
+
+// the file with legacy sections moved and a default section for every plugin added since it
+// was written, and whether that changed it
+fn read_file() -> anyhow::Result<(Value, bool)> {
+    let mut value: Value = serde_yaml::from_reader(File::open(path())?)?;
+    let migrated = migrate_legacy(&mut value)?;
+    let added = plugin::config::add_defaults(plugins_of(&mut value)?);
+    Ok((value, migrated || added))
+}
+
+// the file overridden by the environment, with the secret files read in
+fn parse(mut value: Value) -> anyhow::Result<Config> {
+    let ids = plugin::config::ids();
+    for warning in apply_env(&mut value, &default_config()?, &ids, std::env::vars()) {
+        warn!("{}", warning);
+    }
+    read_secret_files(&mut value)?;
+    let config: Config = serde_path_to_error::deserialize(value)
+        .map_err(|e| anyhow::anyhow!("{}: {}", e.path(), e.inner()))?;
+    plugin::config::check(&config.plugins)?;
+    Ok(config)
+}
+
+// relative sqlite files live in the data directory
+pub fn database_url(url: &str) -> String {
+    match url.strip_prefix("sqlite://") {
+        Some(file) if Path::new(file).is_relative() => {
+            format!("sqlite://{}", cli::ARGS.data_dir.join(file).display())
+        }
+        _ => url.to_string(),
+    }
+}
+
+// what config.yml is written as when there is none
+fn default_config() -> anyhow::Result<Value> {
+    let mut value = serde_yaml::to_value(Config::default())?;
+    plugin::config::add_defaults(plugins_of(&mut value)?);
+    Ok(value)
+}
+
+fn plugins_of(value: &mut Value) -> anyhow::Result<&mut Mapping> {
+    let Value::Mapping(root) = value else {
+        return Err(anyhow::anyhow!("配置文件的顶层必须是映射"));
+    };
+    let plugins = root
+        .entry("plugins".into())
+        .or_insert(Value::Mapping(Mapping::new()));
+    if plugins.is_null() {
+        *plugins = Value::Mapping(Mapping::new());
+    }
+    plugins
+        .as_mapping_mut()
+        .ok_or_else(|| anyhow::anyhow!("plugins: 必须是映射"))
+}
+
+// move the legacy sections into their plugin's section, returns whether there was one
+fn migrate_legacy(value: &mut Value) -> anyhow::Result<bool> {
+    let mut migrated = false;
+    for LegacySection {
+        name: section,
+        plugin: id,
+        renames,
+    } in LEGACY_SECTIONS
+    {
+        let Some(old) = value.as_mapping_mut().and_then(|root| root.remove(section)) else {
+            continue;
+        };
+        let old = match old {
+            Value::Mapping(old) => old,
+            Value::Null => Mapping::new(),
+            _ => return Err(anyhow::anyhow!("{}: 必须是映射", section)),
+        };
+        let plugins = plugins_of(value)?;
+        let target = plugins
+            .entry(find_key(plugins, id))
+            .or_insert(Value::Mapping(Mapping::new()));
+        if target.is_null() {
+            *target = Value::Mapping(Mapping::new());
+        }
+        let target = target
+            .as_mapping_mut()
+            .ok_or_else(|| anyhow::anyhow!("plugins.{}: 必须是映射", id))?;
+        for (field, field_value) in old {
+            let renamed = field
+                .as_str()
+                .and_then(|field| renames.iter().find(|(old, _)| *old == field));
+            let field = match renamed {
+                Some((_, new)) => Value::from(*new),
+                None => field,
+            };
+            // the legacy section is still there, so it is what the user has been editing,
+            // an empty value was most likely written as a default
+            if let Some(replaced) = target.insert(field.clone(), field_value.clone()) {
+                let empty = replaced.is_null() || replaced.as_str() == Some("");
+                if !empty && replaced != field_value {
+                    warn!(
+                        "plugins.{}.{} 已被旧配置节 {} 中的值覆盖",
+                        id,
+                        field.as_str().unwrap_or_default(),
+                        section
+                    );
+                }
+            }
         }
-        Err(_) => {
-            let file = File::create(file_name)?;
-            let default_config = Config::default();
-            serde_yaml::to_writer(file, &default_config).unwrap();
-            Err(anyhow::Error::msg(
-                "配置文件不存在，自动创建默认配置，请更改配置后重启机器人！",
-            ))
+        info!("旧配置节 {} 已移至 plugins.{}", section, id);
+        migrated = true;
+    }
+    Ok(migrated)
+}
+
+// `defaults` tells the known fields apart from typos and, like the plugin `ids`, gives a key
+// not in the file its spelling; returns the warnings about the variables for the caller to log
+fn apply_env(
+    value: &mut Value,
+    defaults: &Value,
+    ids: &[&str],
+    vars: impl Iterator<Item = (String, String)>,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut defaults = defaults.clone();
+    // any driver may be configured, not just the default sqlite
+    if let Some(database) = defaults.get_mut("database") {
+        *database = Value::Mapping(Mapping::new());
+    }
+    for (name, text) in vars {
+        let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if CLI_ENV.contains(&name.as_str()) {
+            continue;
         }
+        // the case is kept for the keys of maps, like the plugin ids in `dispatch.timeouts`
+        let keys = path.split("__").map(str::to_string).collect::<Vec<_>>();
+        let keys = match legacy_path(&keys) {
+            Some(moved) => {
+                warnings.push(format!(
+                    "环境变量 {} 已过时，请改用 {}{}",
+                    name,
+                    ENV_PREFIX,
+                    moved.join("__").to_uppercase()
+                ));
+                moved
+            }
+            None => keys,
+        };
+        if !is_known(value, &keys) && !is_known(&defaults, &keys) {
+            warnings.push(format!("环境变量 {} 不对应任何配置项，请检查拼写", name));
+        }
+        set_path(value, Some(&defaults), ids, &keys, text);
+    }
+    warnings
+}
+
+// SIMPLEBOT_SAUCENAO__API_KEY still reaches plugins.Search.saucenao_api_key
+fn legacy_path(keys: &[String]) -> Option<Vec<String>> {
+    let (first, rest) = keys.split_first()?;
+    let legacy = LEGACY_SECTIONS
+        .iter()
+        .find(|legacy| legacy.name.eq_ignore_ascii_case(first))?;
+    let mut moved = vec!["plugins".to_string(), legacy.plugin.to_string()];
+    moved.extend(rest.iter().map(|key| {
+        match legacy.renames.iter().find(|(old, _)| old.eq_ignore_ascii_case(key)) {
+            Some((_, new)) => new.to_string(),
+            None => key.clone(),
+        }
+    }));
+    Some(moved)
+}
+
+// whether the keys lead to a field of `value`, any key of an empty mapping like
+// `dispatch.timeouts` is taken as an entry of it
+fn is_known(value: &Value, keys: &[String]) -> bool {
+    let Some((first, rest)) = keys.split_first() else {
+        return true;
+    };
+    let Value::Mapping(mapping) = value else {
+        return false;
+    };
+    if mapping.is_empty() {
+        return true;
+    }
+    // a secret file stands in for its field
+    let field = match rest.is_empty() {
+        true => first.strip_suffix(FILE_SUFFIX).unwrap_or(first),
+        false => first,
+    };
+    [first.as_str(), field]
+        .iter()
+        .filter_map(|key| mapping.get(find_key(mapping, key)))
+        .any(|child| is_known(child, rest))
+}
+
+// `defaults` is followed along with `value` to spell the keys missing from the file
+fn set_path(value: &mut Value, defaults: Option<&Value>, ids: &[&str], keys: &[String], text: String) {
+    let Some((last, parents)) = keys.split_last() else {
+        return;
+    };
+    let mut current = value;
+    let mut defaults = defaults;
+    for key in parents {
+        if !current.is_mapping() {
+            *current = Value::Mapping(Mapping::new());
+        }
+        let mapping = current.as_mapping_mut().unwrap();
+        let key = spell_key(mapping, defaults, ids, key);
+        defaults = defaults.and_then(|defaults| defaults.get(&key));
+        current = mapping
+            .entry(key)
+            .or_insert(Value::Mapping(Mapping::new()));
+    }
+    if !current.is_mapping() {
+        *current = Value::Mapping(Mapping::new());
+    }
+    let mapping = current.as_mapping_mut().unwrap();
+    let key = spell_key(mapping, defaults, ids, last);
+    let default = defaults.and_then(|defaults| defaults.get(&key));
+    let value = match mapping.get(&key).or(default) {
+        // a password like 123456 stays a string, also when only the default has it
+        Some(Value::String(_)) => Value::String(text),
+        _ => serde_yaml::from_str(&text).unwrap_or(Value::String(text)),
+    };
+    mapping.insert(key, value);
+}
+
+// the existing key regardless of case, so SIMPLEBOT_PLUGINS__SEARCH__... finds `Search`
+fn find_key(mapping: &Mapping, key: &str) -> Value {
+    existing_key(mapping, key).unwrap_or_else(|| key.into())
+}
+
+fn existing_key(mapping: &Mapping, key: &str) -> Option<Value> {
+    mapping
+        .keys()
+        .find(|existing| {
+            existing
+                .as_str()
+                .is_some_and(|existing| existing.eq_ignore_ascii_case(key))
+        })
+        .cloned()
+}
+
+// a key is spelled as in the file, else as in the defaults, else as the plugin id it names,
+// else as written, like the `plugin::action` keys of `rate_limits`
+fn spell_key(mapping: &Mapping, defaults: Option<&Value>, ids: &[&str], key: &str) -> Value {
+    existing_key(mapping, key)
+        .or_else(|| defaults?.as_mapping().and_then(|defaults| existing_key(defaults, key)))
+        .or_else(|| {
+            let id = ids.iter().find(|id| id.eq_ignore_ascii_case(key))?;
+            Some(Value::from(*id))
+        })
+        .unwrap_or_else(|| key.into())
+}
+
+fn read_secret_files(value: &mut Value) -> anyhow::Result<()> {
+    let Value::Mapping(mapping) = value else {
+        return Ok(());
+    };
+    let files = mapping
+        .iter()
+        .filter_map(|(key, file)| {
+            let key = key.as_str()?;
+            let secret = key.strip_suffix(FILE_SUFFIX)?;
+            Some((key.to_string(), secret.to_string(), file.as_str()?.to_string()))
+        })
+        .collect::<Vec<_>>();
+    for (key, secret, file) in files {
+        let content = std::fs::read_to_string(&file)
+            .map_err(|e| anyhow::anyhow!("{}: 无法读取 {}: {}", key, file, e))?;
+        mapping.remove(key.as_str());
+        mapping.insert(
+            secret.into(),
+            Value::String(content.trim_end_matches(['\r', '\n']).to_string()),
+        );
+    }
+    for (_, child) in mapping.iter_mut() {
+        read_secret_files(child)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULTS: &str = "
+account:
+  password: ''
+dispatch:
+  timeouts: {}
+rate_limits: {}
+plugins:
+  Search:
+    saucenao_api_key: ''
+";
+
+    fn apply(file: &str, vars: &[(&str, &str)]) -> (Value, Vec<String>) {
+        let mut value = serde_yaml::from_str(file).unwrap();
+        let defaults = serde_yaml::from_str(DEFAULTS).unwrap();
+        let vars = vars
+            .iter()
+            .map(|(name, text)| (name.to_string(), text.to_string()));
+        let warnings = apply_env(&mut value, &defaults, &["Search", "KeyWord"], vars);
+        (value, warnings)
+    }
+
+    #[test]
+    fn env_keeps_the_spelling_of_keys() {
+        let (value, warnings) = apply(
+            "account: {}\ndispatch: {}",
+            &[
+                ("SIMPLEBOT_ACCOUNT__PASSWORD", "123456"),
+                ("SIMPLEBOT_DISPATCH__TIMEOUTS__KEYWORD", "5"),
+                ("SIMPLEBOT_DISPATCH__TIMEOUTS__Repeater", "7"),
+                ("SIMPLEBOT_RATE_LIMITS__KeyWord::add", "1/10s"),
+            ],
+        );
+        assert_eq!(value["account"]["password"], Value::from("123456"));
+        assert_eq!(value["dispatch"]["timeouts"]["KeyWord"], Value::from(5));
+        assert_eq!(value["dispatch"]["timeouts"]["Repeater"], Value::from(7));
+        assert_eq!(value["rate_limits"]["KeyWord::add"], Value::from("1/10s"));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn env_warns_about_unknown_overrides() {
+        let (_, warnings) = apply(
+            "account:\n  password: ''",
+            &[
+                ("SIMPLEBOT_ACCOUNT__PASSWROD", "x"),
+                ("SIMPLEBOT_PLUGINS__SEARCH__SAUCENAO_API_KEY", "key"),
+                ("SIMPLEBOT_CONFIG", "other.yml"),
+                ("OTHER_ACCOUNT__PASSWORD", "x"),
+            ],
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("SIMPLEBOT_ACCOUNT__PASSWROD"));
+    }
+
+    #[test]
+    fn env_maps_legacy_paths() {
+        let (value, warnings) = apply(
+            "plugins:\n  Search:\n    saucenao_api_key: ''",
+            &[("SIMPLEBOT_SAUCENAO__API_KEY", "key")],
+        );
+        assert_eq!(value["plugins"]["Search"]["saucenao_api_key"], Value::from("key"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("SIMPLEBOT_PLUGINS__SEARCH__SAUCENAO_API_KEY"));
+    }
+
+    #[test]
+    fn secret_files_replace_their_field() {
+        let file = std::env::temp_dir().join(format!("simple_bot_secret_{}", std::process::id()));
+        std::fs::write(&file, "pw\n").unwrap();
+        let mut value: Value = serde_yaml::from_str(&format!(
+            "plugins:\n  Div:\n    ubi_password_file: {}",
+            file.display()
+        ))
+        .unwrap();
+        read_secret_files(&mut value).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        let div = &value["plugins"]["Div"];
+        assert_eq!(div["ubi_password"], Value::from("pw"));
+        assert!(div.get("ubi_password_file").is_none());
+        // the key names the missing file
+        let error = read_secret_files(&mut serde_yaml::from_str("token_file: /nonexistent").unwrap())
+            .unwrap_err();
+        assert!(error.to_string().starts_with("token_file:"));
     }
 }
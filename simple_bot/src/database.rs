@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use rbatis::RBatis;
+
+use crate::cli;
+use crate::config;
+
+lazy_static! {
+    // chosen once at startup like the connection itself, a reload does not change it
+    pub static ref DIALECT: Dialect =
+        Dialect::selected(&crate::CONFIG.load().database).unwrap();
+}
+
+// the database picked by the only key of `Config::database`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+    MySql,
+}
+
+impl Dialect {
+    const ALL: [Dialect; 3] = [Dialect::Sqlite, Dialect::Postgres, Dialect::MySql];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "sqlite",
+            Dialect::Postgres => "postgres",
+            Dialect::MySql => "mysql",
+        }
+    }
+
+    fn selected(database: &HashMap<String, String>) -> anyhow::Result<Dialect> {
+        let mut selected = Dialect::ALL
+            .into_iter()
+            .filter(|dialect| database.contains_key(dialect.key()));
+        match (selected.next(), selected.next()) {
+            (Some(dialect), None) => Ok(dialect),
+            (None, _) => Err(anyhow::anyhow!(
+                "没有选择数据库，请在 database 中配置 sqlite、postgres 或 mysql 之一"
+            )),
+            (Some(_), Some(_)) => Err(anyhow::anyhow!("database 中只能配置一个数据库")),
+        }
+    }
+
+    // `INSERT ... ON CONFLICT DO NOTHING`, which MySQL spells `INSERT IGNORE`
+    pub fn insert_ignore(&self, table: &str, columns: &[&str]) -> String {
+        let insert = format!(
+            "INTO {} ({}) VALUES ({})",
+            table,
+            columns.join(", "),
+            placeholders(columns.len())
+        );
+        match self {
+            Dialect::MySql => format!("INSERT IGNORE {}", insert),
+            _ => format!("INSERT {} ON CONFLICT DO NOTHING", insert),
+        }
+    }
+
+    // insert, or overwrite `updates` of the row already holding the same `keys`
+    pub fn upsert(&self, table: &str, columns: &[&str], keys: &[&str], updates: &[&str]) -> String {
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            columns.join(", "),
+            placeholders(columns.len())
+        );
+        match self {
+            Dialect::MySql => {
+                let updates = updates
+                    .iter()
+                    .map(|column| format!("{0} = VALUES({0})", column))
+                    .collect::<Vec<_>>();
+                format!("{} ON DUPLICATE KEY UPDATE {}", insert, updates.join(", "))
+            }
+            _ => {
+                let updates = updates
+                    .iter()
+                    .map(|column| format!("{0} = excluded.{0}", column))
+                    .collect::<Vec<_>>();
+                format!(
+                    "{} ON CONFLICT ({}) DO UPDATE SET {}",
+                    insert,
+                    keys.join(", "),
+                    updates.join(", ")
+                )
+            }
+        }
+    }
+}
+
+// `?` works with every driver, rbatis rewrites it to `$1` for Postgres
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+// postgres and mysql are behind the cargo features of the same name
+pub fn connect(db: &RBatis) -> anyhow::Result<()> {
+    let config = crate::CONFIG.load();
+    let url = &config.database[DIALECT.key()];
+    match *DIALECT {
+        Dialect::Sqlite => {
+            std::fs::create_dir_all(&cli::ARGS.data_dir)?;
+            db.init(
+                rbdc_sqlite::driver::SqliteDriver {},
+                &config::database_url(url),
+            )?;
+        }
+        #[cfg(feature = "postgres")]
+        Dialect::Postgres => {
+            db.init(rbdc_pg::driver::PgDriver {}, url)?;
+        }
+        #[cfg(feature = "mysql")]
+        Dialect::MySql => {
+            db.init(rbdc_mysql::driver::MysqlDriver {}, url)?;
+        }
+        #[allow(unreachable_patterns)]
+        dialect => {
+            return Err(anyhow::anyhow!(
+                "使用 {0} 数据库需要启用 {0} 功能重新编译，如 cargo build --features {0}",
+                dialect.key()
+            ))
+        }
+    }
+    Ok(())
+}
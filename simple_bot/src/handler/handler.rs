@@ -1,16 +1,11 @@
+use std::time::Duration;
+
 use proc_qq::*;
 
 use crate::future::SESSION_CACHE;
+use crate::handler::supervisor::{spawn_and_wait, supervise};
 use crate::plugin;
 
-macro_rules! on_event {
-    ($plugins:expr, $event:expr) => {
-        for plugin in $plugins {
-            let _ = plugin.on_event($event).await;
-        }
-    };
-}
-
 #[event]
 pub async fn message_handler(event: &MessageEvent) -> anyhow::Result<bool> {
     let message_chain = event.message_chain();
@@ -20,7 +15,29 @@ pub async fn message_handler(event: &MessageEvent) -> anyhow::Result<bool> {
             let _ = tx.send(message_chain.clone());
         }
     }
-    on_event!(plugin::RAW_PLUGINS.as_ref(), event);
-    on_event!(plugin::COMMAND_PLUGINS.as_ref(), event);
+    let wait = Duration::from_millis(crate::CONFIG.load().dispatch.wait_millis);
+    for hook in plugin::DISPATCH_ORDER.iter() {
+        if !plugin::switch::is_enabled(event, hook.get_id()).await {
+            continue;
+        }
+        match hook.claims(event) {
+            Some(false) => continue,
+            // the matching action runs in the background, however long it takes
+            Some(true) => {
+                let event = event.clone();
+                tokio::spawn(supervise(hook.get_id(), async move {
+                    hook.on_event(&event).await
+                }));
+                break;
+            }
+            None => {
+                let event = event.clone();
+                let future = async move { hook.on_event(&event).await };
+                if spawn_and_wait(hook.get_id(), future, wait).await && hook.is_consuming() {
+                    break;
+                }
+            }
+        }
+    }
     Ok(true)
 }
@@ -1,3 +1,7 @@
 pub use handler::message_handler;
+pub use notice::*;
+pub use supervisor::supervise;
 
 mod handler;
+mod notice;
+mod supervisor;
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use proc_qq::*;
+use tracing::error;
+
+use crate::handler::supervisor::spawn_and_wait;
+use crate::plugin::{self, NoticePlugin, Plugin, PluginContext, RequestPlugin};
+
+// hand a notice or a request to the plugins by priority until one consumes it, each call in a
+// task of its own like the messages; events of a group skip the plugins switched off there
+async fn dispatch<P, E>(
+    plugins: &[&'static P],
+    group: Option<i64>,
+    event: &E,
+    call: impl Fn(&'static P, &'static PluginContext, E) -> BoxFuture<'static, anyhow::Result<bool>>,
+) -> anyhow::Result<bool>
+where
+    P: Plugin + ?Sized,
+    E: Clone,
+{
+    let wait = Duration::from_millis(crate::CONFIG.load().dispatch.wait_millis);
+    for plugin in plugins.iter().copied() {
+        if let Some(group) = group {
+            if !plugin::switch::is_enabled_in_group(group, plugin.get_id()).await {
+                continue;
+            }
+        }
+        let context = match plugin::context::get(plugin.get_id()) {
+            Ok(context) => context,
+            Err(e) => {
+                error!("{:?}", e);
+                continue;
+            }
+        };
+        let future = call(plugin, context, event.clone());
+        if spawn_and_wait(plugin.get_id(), future, wait).await && plugin.is_consuming() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn notice_plugins() -> &'static [&'static (dyn NoticePlugin + Send + Sync)] {
+    &plugin::NOTICE_PLUGINS
+}
+
+fn request_plugins() -> &'static [&'static (dyn RequestPlugin + Send + Sync)] {
+    &plugin::REQUEST_PLUGINS
+}
+
+#[event]
+pub async fn member_join_handler(event: &NewMemberEvent) -> anyhow::Result<bool> {
+    dispatch(
+        notice_plugins(),
+        Some(event.inner.group_code),
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_member_join(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
+
+#[event]
+pub async fn member_leave_handler(event: &GroupLeaveEvent) -> anyhow::Result<bool> {
+    dispatch(
+        notice_plugins(),
+        Some(event.inner.group_code),
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_member_leave(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
+
+#[event]
+pub async fn group_recall_handler(event: &GroupMessageRecallEvent) -> anyhow::Result<bool> {
+    dispatch(
+        notice_plugins(),
+        Some(event.inner.group_code),
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_group_recall(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
+
+#[event]
+pub async fn friend_recall_handler(event: &FriendMessageRecallEvent) -> anyhow::Result<bool> {
+    dispatch(
+        notice_plugins(),
+        None,
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_friend_recall(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
+
+#[event]
+pub async fn group_mute_handler(event: &GroupMuteEvent) -> anyhow::Result<bool> {
+    dispatch(
+        notice_plugins(),
+        Some(event.inner.group_code),
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_group_mute(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
+
+#[event]
+pub async fn friend_poke_handler(event: &FriendPokeEvent) -> anyhow::Result<bool> {
+    dispatch(
+        notice_plugins(),
+        None,
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_friend_poke(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
+
+#[event]
+pub async fn friend_request_handler(event: &NewFriendRequestEvent) -> anyhow::Result<bool> {
+    dispatch(
+        request_plugins(),
+        None,
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_friend_request(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
+
+#[event]
+pub async fn group_invitation_handler(event: &SelfInvitedEvent) -> anyhow::Result<bool> {
+    // the bot is not in the group yet, so there is no switch to look at
+    dispatch(
+        request_plugins(),
+        None,
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_group_invitation(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
+
+#[event]
+pub async fn join_request_handler(event: &GroupRequestEvent) -> anyhow::Result<bool> {
+    dispatch(
+        request_plugins(),
+        Some(event.inner.group_code),
+        event,
+        |plugin, context, event| {
+            async move { plugin.on_join_request(&event, context).await }.boxed()
+        },
+    )
+    .await
+}
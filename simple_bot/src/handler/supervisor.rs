@@ -0,0 +1,63 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+use futures::FutureExt;
+use tracing::{error, warn};
+
+// run a plugin call in a task of its own and wait up to `wait` for its answer, used for every
+// event whose handling depends on the answer; a call taking longer goes on in the background
+// and counts as not handled, so the next plugins are not held up
+pub async fn spawn_and_wait<F>(plugin: &'static str, future: F, wait: Duration) -> bool
+where
+    F: Future<Output = anyhow::Result<bool>> + Send + 'static,
+{
+    let task = tokio::spawn(supervise(plugin, future));
+    match tokio::time::timeout(wait, task).await {
+        Ok(handled) => handled.unwrap_or(false),
+        Err(_) => {
+            warn!(
+                "插件 {} 处理事件超过 {} 毫秒，继续交给后续插件",
+                plugin,
+                wait.as_millis()
+            );
+            false
+        }
+    }
+}
+
+// run one plugin call so that a timeout or a panic only ends that call,
+// returns whether the plugin reports the event as handled
+pub async fn supervise<F>(plugin: &'static str, future: F) -> bool
+where
+    F: Future<Output = anyhow::Result<bool>> + Send,
+{
+    let future = AssertUnwindSafe(future).catch_unwind();
+    let timeout = crate::CONFIG.load().dispatch.get_timeout(plugin);
+    let result = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, future).await {
+            Ok(result) => result,
+            Err(_) => {
+                error!("插件 {} 执行超时({}s)，已取消", plugin, timeout.as_secs());
+                return false;
+            }
+        },
+        None => future.await,
+    };
+    match result {
+        Ok(Ok(handled)) => handled,
+        Ok(Err(e)) => {
+            warn!("插件 {} 返回错误: {:?}", plugin, e);
+            false
+        }
+        Err(panic) => {
+            let reason = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            error!("插件 {} 执行时崩溃: {}", plugin, reason);
+            false
+        }
+    }
+}
@@ -2,6 +2,9 @@
 #![allow(hidden_glob_reexports)]
 
 
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
 use lazy_static::lazy_static;
 pub use proc_qq::re_exports::*;
 pub use proc_qq::*;
@@ -9,35 +12,41 @@ use rbatis::RBatis;
 use tracing::*;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use handler::message_handler;
+use handler::*;
 
 use crate::config::Config;
-use crate::tokio::{self, sync::Mutex};
+use crate::tokio;
 
+mod cli;
 mod config;
+mod database;
 mod future;
 mod handler;
+mod migration;
 pub mod model;
 mod plugin;
+mod repository;
 
 lazy_static! {
-    static ref CONFIG: Config = config::read_config().unwrap();
-    static ref RB: std::sync::Arc<Mutex<RBatis>> = {
+    // swapped as a whole by `plugin::reload`, read it again instead of holding on to it
+    static ref CONFIG: ArcSwap<Config> = ArcSwap::from_pointee(config::read_config().unwrap());
+    // a pool, clone it instead of sharing a connection
+    static ref RB: RBatis = {
         let db = RBatis::new();
-        let db_config = &CONFIG.database;
-        if db_config.contains_key("sqlite") {
-            db.init(rbdc_sqlite::driver::SqliteDriver {}, &db_config["sqlite"])
-        } else {
-            panic!("no selected database")
-        }
-        .expect("database init error");
-        std::sync::Arc::new(Mutex::new(db))
+        database::connect(&db).expect("database init error");
+        db
     };
 }
 
 #[tokio::main]
 async fn main() {
     init_logger();
+    if !cli::ARGS.memory {
+        if let Err(e) = migration::migrate(&RB).await {
+            error!("{:#}", e);
+            return;
+        }
+    }
     let builder = ClientBuilder::new();
     let client = builder
         // .authentication(Authentication::UinPassword(
@@ -45,17 +54,41 @@ async fn main() {
         //     CONFIG.account.password.clone(),
         // ))
         .authentication(proc_qq::Authentication::QRCode)
-        .version(parse_protocol(CONFIG.account.protocol.clone()))
+        .version(parse_protocol(CONFIG.load().account.protocol.clone()))
         .show_slider_pop_menu_if_possible()
-        .modules(vec![module!("simple_bot", "handler", message_handler)])
+        .modules(vec![module!(
+            "simple_bot",
+            "handler",
+            message_handler,
+            member_join_handler,
+            member_leave_handler,
+            group_recall_handler,
+            friend_recall_handler,
+            group_mute_handler,
+            friend_poke_handler,
+            friend_request_handler,
+            group_invitation_handler,
+            join_request_handler
+        )])
         .show_rq(Some(ShowQR::OpenBySystem))
         .build()
         .await
         .unwrap();
-    run_client(client.into())
-        .await
-        .unwrap();
-        // .expect("启动时出现错误");
+    let client = Arc::new(client);
+    if let Err(e) = plugin::lifecycle::load(client.rq_client.clone()).await {
+        error!("{:#}", e);
+        return;
+    }
+    plugin::reload::watch();
+    // in a task of its own, so the client stays connected while the plugins shut down
+    let mut running = tokio::spawn(run_client(client.clone()));
+    tokio::select! {
+        result = &mut running => result.unwrap().unwrap(),
+        _ = plugin::lifecycle::shutdown_signal() => info!("收到退出信号，正在关闭插件"),
+    }
+    plugin::lifecycle::shutdown(CONFIG.load().dispatch.get_shutdown_deadline()).await;
+    running.abort();
+    drop(client);
 }
 
 fn parse_protocol(protocol: String) -> &'static ricq::version::Version {
@@ -77,9 +110,9 @@ fn init_logger() {
         )
         .with(
             tracing_subscriber::filter::Targets::new()
-                .with_target("ricq", Level::DEBUG)
-                .with_target("proc_qq", Level::DEBUG)
-                .with_target("simple_bot", Level::DEBUG),
+                .with_target("ricq", cli::ARGS.log_level)
+                .with_target("proc_qq", cli::ARGS.log_level)
+                .with_target("simple_bot", cli::ARGS.log_level),
         )
         .init();
 }
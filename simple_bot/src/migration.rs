@@ -0,0 +1,115 @@
+use rbatis::executor::RBatisTxExecutor;
+use rbatis::RBatis;
+use tracing::info;
+
+use crate::database::{Dialect, DIALECT};
+use crate::model::schema::SchemaVersion;
+
+// one file per dialect under migrations/<dialect>/, since the column types differ
+struct Migration {
+    version: i64,
+    name: &'static str,
+    sqlite: &'static str,
+    postgres: &'static str,
+    mysql: &'static str,
+}
+
+impl Migration {
+    fn sql(&self, dialect: Dialect) -> &'static str {
+        match dialect {
+            Dialect::Sqlite => self.sqlite,
+            Dialect::Postgres => self.postgres,
+            Dialect::MySql => self.mysql,
+        }
+    }
+}
+
+macro_rules! migration {
+    ($version:literal, $name:literal, $file:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sqlite: include_str!(concat!("../migrations/sqlite/", $file)),
+            postgres: include_str!(concat!("../migrations/postgres/", $file)),
+            mysql: include_str!(concat!("../migrations/mysql/", $file)),
+        }
+    };
+}
+
+// applied in order and recorded in schema_version, only ever append to this list
+const MIGRATIONS: &[Migration] = &[
+    migration!(1, "initial", "0001_initial.sql"),
+    migration!(2, "plugin_kv", "0002_plugin_kv.sql"),
+    migration!(3, "keyword_enabled", "0003_keyword_enabled.sql"),
+];
+
+// bring the database up to the latest schema, refusing one written by a newer version
+pub async fn migrate(db: &RBatis) -> anyhow::Result<()> {
+    db.exec(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version integer not null primary key,
+            name varchar(128) not null,
+            applied_at timestamp not null default CURRENT_TIMESTAMP
+        )
+        "#,
+        vec![],
+    )
+    .await?;
+    let current = SchemaVersion::select_all(db)
+        .await?
+        .iter()
+        .map(|applied| applied.version)
+        .max()
+        .unwrap_or(0);
+    let latest = MIGRATIONS.last().map_or(0, |migration| migration.version);
+    if current > latest {
+        return Err(anyhow::anyhow!(
+            "数据库版本 {} 比程序支持的版本 {} 新，请升级机器人",
+            current,
+            latest
+        ));
+    }
+    // MySQL commits DDL implicitly, a failed migration there may be left half applied
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > current)
+    {
+        let mut tx = db.acquire_begin().await?;
+        if let Err(e) = apply(&mut tx, migration).await {
+            let _ = tx.rollback().await;
+            return Err(anyhow::anyhow!(
+                "数据库迁移 {:04}_{} 失败: {}",
+                migration.version,
+                migration.name,
+                e
+            ));
+        }
+        tx.commit().await?;
+        info!(
+            "已执行数据库迁移 {:04}_{}",
+            migration.version, migration.name
+        );
+    }
+    Ok(())
+}
+
+async fn apply(tx: &mut RBatisTxExecutor, migration: &Migration) -> anyhow::Result<()> {
+    for statement in migration
+        .sql(*DIALECT)
+        .split(';')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    {
+        tx.exec(statement, vec![]).await?;
+    }
+    tx.exec(
+        "INSERT INTO schema_version (version, name) VALUES (?, ?)",
+        vec![
+            rbs::to_value!(migration.version),
+            rbs::to_value!(migration.name),
+        ],
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,10 @@
+use rbatis::crud;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Blacklist {
+    pub group_id: i64,
+    pub user_id: i64,
+}
+
+crud!(Blacklist {});
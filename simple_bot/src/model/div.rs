@@ -1,10 +1,8 @@
-use crate::RB;
 use rbatis::{crud, impl_select};
 use rbatis::rbdc::datetime::DateTime;
 use serde_json::Value;
 use serde::{Serialize, Deserialize};
 use std::fmt::{Display, Formatter};
-use std::ops::DerefMut;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct D1PlayerStats {
@@ -101,28 +99,6 @@ pub struct UbiUser {
     pub ts: DateTime,
 }
 
-impl UbiUser {
-    pub async fn store_user_name(id: &str, name: &str) -> anyhow::Result<()> {
-        let db = RB.lock().await;
-        db.exec(r#"
-            INSERT INTO ubi_user (id, name) VALUES ($1, $2) ON CONFLICT DO NOTHING
-            "#,
-            vec![rbs::to_value!(id), rbs::to_value!(name)]
-        ).await?;
-        Ok(())
-    }
-    pub async fn get_user_id_by_name(name: &str) -> anyhow::Result<Vec<String>> {
-        let mut db = RB.lock().await;
-        let user = UbiUser::select_by_name(db.deref_mut(), &name).await?;
-        Ok(user.iter().map(|x| x.id.clone()).collect())
-    }
-    pub async fn get_user_names_by_id(id: &str) -> anyhow::Result<Vec<String>> {
-        let mut db = RB.lock().await;
-        let users = UbiUser::select_by_id(db.deref_mut(), &id).await?;
-        Ok(users.iter().map(|x| x.name.clone()).collect())
-    }
-}
-
 crud!(UbiUser {});
 impl_select!(UbiUser{select_by_name(name: &str) => "`where lower(name) = lower(#{name})`"});
 impl_select!(UbiUser{select_by_id(id: &str) => "`where id = #{id}`"});
@@ -1,13 +1,15 @@
 use rbatis::crud;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct KeyWord {
     pub id: Option<i64>,
     pub group_id: i64,
     pub regex: Option<String>,
     pub reply: Option<String>,
     pub chance: i32,
+    // 1 or 0, an integer column reads the same with every driver
+    pub enabled: i32,
 }
 
 crud!(KeyWord {});
@@ -0,0 +1,13 @@
+use rbatis::{crud, impl_select};
+use serde::{Deserialize, Serialize};
+
+// a value stored by a plugin through `crate::plugin::context::KvStore`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PluginKv {
+    pub plugin: String,
+    pub name: String,
+    pub value: String,
+}
+
+crud!(PluginKv {});
+impl_select!(PluginKv{select_by_name(plugin: &str, name: &str) => "`where plugin = #{plugin} and name = #{name}`"});
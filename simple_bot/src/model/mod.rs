@@ -1,2 +1,6 @@
 pub mod keyword;
-pub mod div;
\ No newline at end of file
+pub mod div;
+pub mod plugin;
+pub mod blacklist;
+pub mod kv;
+pub mod schema;
\ No newline at end of file
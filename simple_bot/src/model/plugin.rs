@@ -0,0 +1,10 @@
+use rbatis::crud;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DisabledPlugin {
+    pub group_id: i64,
+    pub plugin: String,
+}
+
+crud!(DisabledPlugin {});
@@ -0,0 +1,11 @@
+use rbatis::crud;
+use serde::{Deserialize, Serialize};
+
+// a migration from `crate::migration` applied to this database
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SchemaVersion {
+    pub version: i64,
+    pub name: String,
+}
+
+crud!(SchemaVersion {});
@@ -0,0 +1,102 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::info;
+
+// the type of a plugin's `plugins.<id>` section in config.yml, see `Plugin::get_config_spec`
+#[derive(Clone, Copy)]
+pub struct ConfigSpec {
+    default: fn() -> serde_yaml::Value,
+    parse: fn(&str, serde_yaml::Value) -> anyhow::Result<Arc<dyn Any + Send + Sync>>,
+}
+
+impl ConfigSpec {
+    pub fn of<T>() -> Self
+    where
+        T: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        ConfigSpec {
+            default: || serde_yaml::to_value(T::default()).expect("无法序列化插件的默认配置"),
+            parse: parse_section::<T>,
+        }
+    }
+
+    pub fn default_section(&self) -> serde_yaml::Value {
+        (self.default)()
+    }
+
+    pub fn parse(
+        &self,
+        id: &str,
+        section: serde_yaml::Value,
+    ) -> anyhow::Result<Arc<dyn Any + Send + Sync>> {
+        (self.parse)(id, section)
+    }
+}
+
+// the error names the offending field, like "plugins.Search.min_similarity"
+fn parse_section<T>(
+    id: &str,
+    section: serde_yaml::Value,
+) -> anyhow::Result<Arc<dyn Any + Send + Sync>>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    match serde_path_to_error::deserialize::<_, T>(section) {
+        Ok(config) => Ok(Arc::new(config)),
+        Err(e) => {
+            let path = match e.path().to_string().as_str() {
+                "." => format!("plugins.{}", id),
+                path => format!("plugins.{}.{}", id, path),
+            };
+            Err(anyhow::anyhow!("{}: {}", path, e.into_inner()))
+        }
+    }
+}
+
+// the id of every registered plugin, environment variables may name them in any case
+pub fn ids() -> Vec<&'static str> {
+    super::PLUGINS
+        .iter()
+        .map(|roles| roles.plugin.get_id())
+        .collect()
+}
+
+// add the default of every missing section, returns whether one was added so the
+// caller can write the file back
+pub fn add_defaults(sections: &mut serde_yaml::Mapping) -> bool {
+    let mut added = false;
+    for roles in super::PLUGINS.iter() {
+        let id = roles.plugin.get_id();
+        let Some(spec) = roles.plugin.get_config_spec() else {
+            continue;
+        };
+        if !sections.contains_key(id) {
+            info!("插件 {} 缺少配置，已写入默认值", id);
+            sections.insert(id.into(), spec.default_section());
+            added = true;
+        }
+    }
+    added
+}
+
+// parse every section, reporting all invalid ones at once
+pub fn check(sections: &BTreeMap<String, serde_yaml::Value>) -> anyhow::Result<()> {
+    let mut errors = Vec::new();
+    for roles in super::PLUGINS.iter() {
+        let id = roles.plugin.get_id();
+        let (Some(spec), Some(section)) = (roles.plugin.get_config_spec(), sections.get(id)) else {
+            continue;
+        };
+        if let Err(e) = spec.parse(id, section.clone()) {
+            errors.push(e.to_string());
+        }
+    }
+    if !errors.is_empty() {
+        return Err(anyhow::anyhow!("插件配置无效:\n{}", errors.join("\n")));
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use lazy_static::lazy_static;
+use proc_qq::re_exports::ricq;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use crate::plugin::scheduler::Scheduler;
+use crate::repository::{KvRepo, Repos};
+
+lazy_static! {
+    // one HTTP client for every plugin, so they share its connection pool
+    static ref HTTP: reqwest::Client = reqwest::Client::new();
+    // `--memory` keeps every table in memory, without touching the database
+    static ref REPOS: Repos = if crate::cli::ARGS.memory {
+        Repos::memory()
+    } else {
+        Repos::sql(crate::RB.clone())
+    };
+    // filled by `init` before any plugin is loaded
+    static ref CONTEXTS: OnceCell<HashMap<&'static str, PluginContext>> = OnceCell::new();
+}
+
+// create the context of every registered plugin, parsing its section of config.yml
+pub fn init(client: Arc<ricq::Client>) -> anyhow::Result<()> {
+    let mut contexts = HashMap::new();
+    for roles in super::PLUGINS.iter() {
+        let id = roles.plugin.get_id();
+        let config = match roles.plugin.get_config_spec() {
+            Some(spec) => {
+                let section = crate::CONFIG.load().plugins.get(id).cloned();
+                Some(spec.parse(id, section.unwrap_or_else(|| spec.default_section()))?)
+            }
+            None => None,
+        };
+        contexts.insert(id, PluginContext::new(id, client.clone(), REPOS.clone(), config, HTTP.clone()));
+    }
+    CONTEXTS
+        .set(contexts)
+        .map_err(|_| anyhow::anyhow!("插件上下文已经初始化"))
+}
+
+// everything a plugin works with, handed to each of its hooks instead of global statics
+#[derive(Clone)]
+pub struct PluginContext {
+    pub id: &'static str,
+    // for messages not answering an event, like a reminder; it logs in after `on_load`
+    pub client: Arc<ricq::Client>,
+    pub repos: Repos,
+    pub http: reqwest::Client,
+    pub scheduler: Scheduler,
+    pub kv: KvStore,
+    // the parsed `plugins.<id>` section of config.yml, shared by the clones so a reload reaches all
+    config: Arc<RwLock<Option<Arc<dyn Any + Send + Sync>>>>,
+    // what the plugin keeps between its hooks, one value of each type
+    state: Arc<Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl PluginContext {
+    pub fn new(
+        id: &'static str,
+        client: Arc<ricq::Client>,
+        repos: Repos,
+        config: Option<Arc<dyn Any + Send + Sync>>,
+        http: reqwest::Client,
+    ) -> Self {
+        PluginContext {
+            id,
+            client,
+            scheduler: Scheduler::new(id),
+            kv: KvStore {
+                plugin: id,
+                repo: repos.kv.clone(),
+            },
+            repos,
+            http,
+            config: Arc::new(RwLock::new(config)),
+            state: Arc::default(),
+        }
+    }
+
+    // the config section of the plugin, `T` is the type given to `ConfigSpec::of`; read it
+    // again instead of keeping it to see the changes of `/reload`
+    pub fn config<T: Any + Send + Sync>(&self) -> anyhow::Result<Arc<T>> {
+        self.config
+            .read()
+            .unwrap()
+            .clone()
+            .and_then(|config| config.downcast::<T>().ok())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "插件 {} 没有类型为 {} 的配置",
+                    self.id,
+                    std::any::type_name::<T>()
+                )
+            })
+    }
+
+    // the state of type `T` kept by the plugin, created with `T::default()` on first use
+    pub fn state<T: Any + Default + Send + Sync>(&self) -> Arc<T> {
+        let state = self
+            .state
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Arc::new(T::default()))
+            .clone();
+        // keyed by the type, so always a `T`
+        state.downcast::<T>().unwrap_or_else(|_| unreachable!())
+    }
+
+    pub(super) fn set_config(&self, config: Option<Arc<dyn Any + Send + Sync>>) {
+        *self.config.write().unwrap() = config;
+    }
+}
+
+// the tables behind every context, for the parts of the bot outside any plugin
+pub fn repos() -> &'static Repos {
+    &REPOS
+}
+
+// the context of a registered plugin
+pub fn get(id: &str) -> anyhow::Result<&'static PluginContext> {
+    let contexts = CONTEXTS
+        .get()
+        .ok_or_else(|| anyhow::anyhow!("插件上下文尚未初始化"))?;
+    contexts
+        .get(id)
+        .ok_or_else(|| anyhow::anyhow!("插件 {} 未注册", id))
+}
+
+// persistent values of one plugin, stored as json
+#[derive(Clone)]
+pub struct KvStore {
+    plugin: &'static str,
+    repo: Arc<dyn KvRepo>,
+}
+
+impl KvStore {
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        match self.repo.get(self.plugin, key).await? {
+            Some(value) => Ok(Some(serde_json::from_str(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn set<T: Serialize>(&self, key: &str, value: &T) -> anyhow::Result<()> {
+        let value = serde_json::to_string(value)?;
+        self.repo.set(self.plugin, key, &value).await
+    }
+
+    pub async fn remove(&self, key: &str) -> anyhow::Result<()> {
+        self.repo.remove(self.plugin, key).await
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt::{Display, Formatter};
+
+use proc_qq::{GroupMessageEvent, MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
+use tracing::{error, warn};
+
+// an expected failure, its message is sent back to the user as it is
+#[derive(Debug)]
+pub struct UserError(String);
+
+impl UserError {
+    pub fn new(message: impl Into<String>) -> Self {
+        UserError(message.into())
+    }
+
+    pub fn missing_argument(name: &str, usage: &str) -> Self {
+        UserError(format!("缺少参数 {}\n用法: {}", name, usage))
+    }
+
+    pub fn invalid_argument(name: &str, value: &str, expected: &str, usage: &str) -> Self {
+        UserError(format!(
+            "参数 {} 无效: \"{}\" 不是{}\n用法: {}",
+            name, value, expected, usage
+        ))
+    }
+}
+
+impl Display for UserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UserError {}
+
+pub fn require_group(event: &MessageEvent) -> anyhow::Result<&GroupMessageEvent> {
+    event
+        .as_group_message()
+        .map_err(|_| UserError::new("该命令只能在群聊中使用").into())
+}
+
+// log a failed action and tell the user about it, unexpected errors only get
+// a generic reply and an id to look the full error up in the logs
+pub async fn report(event: &MessageEvent, plugin: &str, pattern: &str, err: anyhow::Error) {
+    let group = event.as_group_message().ok().map(|e| e.inner.group_code);
+    let user = event.from_uin();
+    let reply = match err.downcast_ref::<UserError>() {
+        Some(user_error) => {
+            warn!(plugin, pattern, ?group, user, "{}", user_error);
+            user_error.to_string()
+        }
+        None => {
+            let id = format!("{:08x}", rand::random::<u32>());
+            error!(plugin, pattern, ?group, user, id = %id, "{:?}", err);
+            format!("出现了意外的错误，请联系管理员 (错误编号: {})", id)
+        }
+    };
+    if let Err(e) = event.send_message_to_source(reply.parse_message_chain()).await {
+        error!(plugin, pattern, ?group, user, "发送错误信息失败: {:?}", e);
+    }
+}
@@ -1,7 +1,3 @@
 mod keyword;
 mod remind;
 mod repeater;
-
-pub use keyword::KeyWord;
-pub use remind::Remind;
-pub use repeater::Repeater;
@@ -1,23 +1,132 @@
-use std::ops::DerefMut;
+use std::sync::Arc;
+use std::time::Duration;
 
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
+use lazy_static::lazy_static;
 use proc_qq::{
-    MessageChainParseTrait, MessageContentTrait, MessageEvent, MessageSendToSourceTrait,
+    GroupMessageEvent, MessageChainParseTrait, MessageContentTrait, MessageEvent,
+    MessageSendToSourceTrait,
 };
 use rand::Rng;
-use tokio::sync::OnceCell;
+use regex::Regex;
+use tokio::sync::Mutex;
+use tracing::warn;
 
-use simple_bot_macros::{action, make_action};
+use simple_bot_macros::action;
 
 use crate::model::keyword::KeyWord as Model;
-use crate::plugin::{Action, CommandPlugin, Plugin, RawPlugin};
+use crate::repository::KeyWordRepo;
+use crate::plugin::{
+    error, permission, Action, CommandPlugin, Plugin, PluginContext, PluginRegistration, RawPlugin,
+    Roles, ScheduledPlugin, UserError,
+};
+
+// rules shown on one page of `/keyword list`
+const PAGE_SIZE: usize = 10;
+// how often the table is read again, to pick up rows changed outside the bot
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    // shared by the matcher and the commands
+    static ref STORE: KeywordStore = KeywordStore::default();
+}
+
+// a row of key_word with its regex compiled once
+struct Rule {
+    keyword: Model,
+    // None when the stored regex is invalid, such a rule never fires
+    regex: Option<Regex>,
+}
+
+impl Rule {
+    // whether the rule may answer the message, before rolling its chance
+    fn would_fire(&self, group_id: i64, content: &str) -> bool {
+        if self.keyword.enabled == 0
+            || (self.keyword.group_id != group_id && self.keyword.group_id != 0)
+        {
+            return false;
+        }
+        self.regex
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(content))
+    }
+}
+
+#[derive(Default)]
+struct KeywordStore {
+    // replaced as a whole, so a message never sees a half loaded table
+    rules: ArcSwap<Vec<Rule>>,
+    // a command and a tick may reload together, the later read must be stored last
+    reloading: Mutex<()>,
+}
+
+impl KeywordStore {
+    fn rules(&self) -> Arc<Vec<Rule>> {
+        self.rules.load_full()
+    }
+
+    // read the table again, the regexes are only compiled when a row changed
+    async fn reload(&self, repo: &dyn KeyWordRepo) -> anyhow::Result<()> {
+        let _reloading = self.reloading.lock().await;
+        let mut keywords = repo.all().await?;
+        keywords.sort_by_key(|keyword| keyword.id);
+        let current = self.rules.load();
+        if current.len() == keywords.len()
+            && current
+                .iter()
+                .zip(&keywords)
+                .all(|(rule, keyword)| rule.keyword == *keyword)
+        {
+            return Ok(());
+        }
+        let rules = keywords
+            .into_iter()
+            .map(|keyword| {
+                let regex = match Regex::new(keyword.regex.as_deref().unwrap_or_default()) {
+                    Ok(regex) => Some(regex),
+                    Err(e) => {
+                        warn!(
+                            "关键词 {} 的正则表达式无效: {}",
+                            keyword.id.unwrap_or_default(),
+                            e
+                        );
+                        None
+                    }
+                };
+                Rule { keyword, regex }
+            })
+            .collect();
+        self.rules.store(Arc::new(rules));
+        Ok(())
+    }
+}
+
+// a write takes effect at once instead of on the next tick
+async fn refresh(context: &PluginContext) {
+    if let Err(e) = STORE.reload(&*context.repos.keywords).await {
+        warn!("重新读取关键词失败: {:?}", e);
+    }
+}
 
 pub struct KeyWord {
-    keywords: OnceCell<Vec<Model>>,
     actions: Vec<Box<dyn Action>>,
 }
 
+// one instance for every role
+inventory::submit! {
+    PluginRegistration(|| {
+        let keyword = crate::plugin::instance(KeyWord::new());
+        Roles::new(keyword).command(keyword).raw(keyword).scheduled(keyword)
+    })
+}
+
+#[async_trait]
 impl Plugin for KeyWord {
+    fn get_id(&self) -> &str {
+        "KeyWord"
+    }
+
     fn get_name(&self) -> &str {
         "关键词回复"
     }
@@ -25,36 +134,42 @@ impl Plugin for KeyWord {
     fn get_desc(&self) -> &str {
         "通过关键词，一定几率触发特定回复"
     }
+
+    // read the table before the first message instead of while answering it
+    async fn on_load(&self, context: &PluginContext) -> anyhow::Result<()> {
+        STORE.reload(&*context.repos.keywords).await
+    }
+
+    async fn on_reload(&self, context: &PluginContext) -> anyhow::Result<()> {
+        STORE.reload(&*context.repos.keywords).await
+    }
 }
 
 #[async_trait]
 impl RawPlugin for KeyWord {
-    async fn on_event(&self, event: &MessageEvent) -> anyhow::Result<bool> {
-        let event = event.as_group_message()?;
+    async fn on_event(
+        &self,
+        event: &MessageEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        let event = match event.as_group_message() {
+            Ok(event) => event,
+            Err(_) => return Ok(false),
+        };
         let content = event.message_content();
         let mut done = false;
-        for keyword in self.load_keyword().await {
-            let group = event.inner.group_code;
-            if keyword.group_id != group && keyword.group_id != 0 {
-                continue;
-            }
-            let regex = regex::Regex::new(keyword.regex.as_ref().unwrap_or(&String::new()));
-            if regex.is_err() {
-                continue;
-            }
-            let regex = regex.unwrap();
-            if !regex.is_match(&content) {
+        for rule in STORE.rules().iter() {
+            if !rule.would_fire(event.inner.group_code, &content) {
                 continue;
             }
             let point = rand::thread_rng().gen_range(0..=100);
-            if point > keyword.chance {
+            if point > rule.keyword.chance {
                 continue;
             }
-            if let Some(reply) = &keyword.reply {
+            if let Some(reply) = &rule.keyword.reply {
                 event
                     .send_message_to_source(reply.clone().parse_message_chain())
-                    .await
-                    .unwrap();
+                    .await?;
                 done = true;
             }
         }
@@ -66,65 +181,289 @@ impl CommandPlugin for KeyWord {
     fn get_actions(&self) -> &Vec<Box<dyn Action>> {
         &self.actions
     }
+
+    fn get_root(&self) -> Option<&str> {
+        Some("/keyword")
+    }
+}
+
+// rows edited outside the bot show up within a minute
+#[async_trait]
+impl ScheduledPlugin for KeyWord {
+    fn get_interval(&self) -> Duration {
+        REFRESH_INTERVAL
+    }
+
+    async fn on_tick(&self, context: &PluginContext) -> anyhow::Result<()> {
+        STORE.reload(&*context.repos.keywords).await
+    }
 }
 
 impl KeyWord {
     pub fn new() -> Self {
         Self {
-            keywords: OnceCell::new(),
-            actions: vec![make_action!(add_keyword)],
+            actions: crate::plugin::module_actions(module_path!()),
         }
     }
-
-    async fn load_keyword(&self) -> &Vec<Model> {
-        self.keywords
-            .get_or_init(|| async {
-                let mut db = crate::RB.lock().await;
-                Model::select_all(db.deref_mut())
-                    .await
-                    .expect("从数据库读取关键词失败")
-            })
-            .await
-    }
 }
 
-#[action("/keyword add {chance} {keyword} {reply}")]
+/// 添加关键词，chance 为触发几率(0-100)，keyword 为正则表达式
+#[action("/keyword add {chance} {keyword} {reply..}", role = GroupAdmin)]
 async fn add_keyword(
     event: &MessageEvent,
-    chance: Option<i32>,
-    keyword: Option<String>,
-    reply: Option<String>,
+    context: &PluginContext,
+    chance: i32,
+    keyword: String,
+    reply: String,
 ) -> anyhow::Result<bool> {
-    if chance.is_none() || keyword.is_none() || reply.is_none() {
-        event
-            .send_message_to_source("参数不足".parse_message_chain())
-            .await
-            .unwrap();
-        return Ok(false);
-    }
-    let event = event.as_group_message().unwrap();
-    let chance = chance.unwrap();
-    let keyword = keyword.unwrap();
-    let reply = reply.unwrap();
-    let mut db = crate::RB.lock().await;
+    let event = error::require_group(event)?;
+    check_regex(&keyword)?;
     let keyword = Model {
         id: None,
         group_id: event.inner.group_code,
         regex: Some(keyword),
         reply: Some(reply),
         chance,
+        enabled: 1,
     };
-    let result = Model::insert(db.deref_mut(), &keyword).await;
-    if result.is_err() {
+    context.repos.keywords.add(keyword).await?;
+    refresh(context).await;
+    event
+        .send_message_to_source("添加成功".parse_message_chain())
+        .await?;
+    Ok(true)
+}
+
+/// 查看本群与全局的关键词，每页 10 条
+#[action("/keyword list {page:uint}")]
+async fn list_keyword(event: &MessageEvent, page: Option<usize>) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    let rules = STORE.rules();
+    let keywords = rules
+        .iter()
+        .map(|rule| &rule.keyword)
+        .filter(|keyword| keyword.group_id == event.inner.group_code || keyword.group_id == 0)
+        .collect::<Vec<_>>();
+    if keywords.is_empty() {
         event
-            .send_message_to_source("添加失败".parse_message_chain())
-            .await
-            .unwrap();
-        return Ok(false);
+            .send_message_to_source("本群还没有关键词".parse_message_chain())
+            .await?;
+        return Ok(true);
+    }
+    let pages = keywords.len().div_ceil(PAGE_SIZE);
+    let page = page.unwrap_or(1).clamp(1, pages);
+    let mut text = format!("{:=^30}\n", "KEYWORD");
+    for keyword in keywords.iter().skip((page - 1) * PAGE_SIZE).take(PAGE_SIZE) {
+        text.push_str(&describe(keyword));
     }
+    text.push_str(&format!("第 {}/{} 页", page, pages));
     event
-        .send_message_to_source("添加成功".parse_message_chain())
-        .await
-        .unwrap();
+        .send_message_to_source(text.parse_message_chain())
+        .await?;
+    Ok(true)
+}
+
+/// 查看一段消息会触发的关键词
+#[action("/keyword find {text..}")]
+async fn find_keyword(event: &MessageEvent, text: String) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    let matched = STORE
+        .rules()
+        .iter()
+        .filter(|rule| rule.would_fire(event.inner.group_code, &text))
+        .map(|rule| describe(&rule.keyword))
+        .collect::<String>();
+    let reply = if matched.is_empty() {
+        "没有会被触发的关键词".to_string()
+    } else {
+        format!("{:=^30}\n{}", "KEYWORD", matched.trim_end())
+    };
+    event
+        .send_message_to_source(reply.parse_message_chain())
+        .await?;
+    Ok(true)
+}
+
+/// 删除关键词，全局关键词只有机器人主人可以删除
+#[action("/keyword del {id:uint}", role = GroupAdmin)]
+async fn del_keyword(
+    event: &MessageEvent,
+    context: &PluginContext,
+    id: i64,
+) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    find_editable(event, context, id).await?;
+    context.repos.keywords.remove(id).await?;
+    refresh(context).await;
+    event
+        .send_message_to_source(format!("已删除关键词 {}", id).parse_message_chain())
+        .await?;
     Ok(true)
 }
+
+/// 修改关键词，参数与 /keyword add 相同
+#[action("/keyword edit {id:uint} {chance} {keyword} {reply..}", role = GroupAdmin)]
+async fn edit_keyword(
+    event: &MessageEvent,
+    context: &PluginContext,
+    id: i64,
+    chance: i32,
+    keyword: String,
+    reply: String,
+) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    check_regex(&keyword)?;
+    let mut existing = find_editable(event, context, id).await?;
+    existing.chance = chance;
+    existing.regex = Some(keyword);
+    existing.reply = Some(reply);
+    context.repos.keywords.update(existing).await?;
+    refresh(context).await;
+    event
+        .send_message_to_source(format!("已修改关键词 {}", id).parse_message_chain())
+        .await?;
+    Ok(true)
+}
+
+/// 停用关键词，停用后不再触发但仍可在列表中看到
+#[action("/keyword disable {id:uint}", role = GroupAdmin)]
+async fn disable_keyword(
+    event: &MessageEvent,
+    context: &PluginContext,
+    id: i64,
+) -> anyhow::Result<bool> {
+    switch_keyword(event, context, id, false).await
+}
+
+/// 重新启用停用的关键词
+#[action("/keyword enable {id:uint}", role = GroupAdmin)]
+async fn enable_keyword(
+    event: &MessageEvent,
+    context: &PluginContext,
+    id: i64,
+) -> anyhow::Result<bool> {
+    switch_keyword(event, context, id, true).await
+}
+
+async fn switch_keyword(
+    event: &MessageEvent,
+    context: &PluginContext,
+    id: i64,
+    enabled: bool,
+) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    let mut keyword = find_editable(event, context, id).await?;
+    keyword.enabled = enabled as i32;
+    context.repos.keywords.update(keyword).await?;
+    refresh(context).await;
+    event
+        .send_message_to_source(
+            format!("已{}关键词 {}", if enabled { "启用" } else { "停用" }, id)
+                .parse_message_chain(),
+        )
+        .await?;
+    Ok(true)
+}
+
+// a rule of this group, or a global one when the sender is the owner
+async fn find_editable(
+    event: &GroupMessageEvent,
+    context: &PluginContext,
+    id: i64,
+) -> anyhow::Result<Model> {
+    match context.repos.keywords.get(id).await? {
+        Some(keyword) if keyword.group_id == event.inner.group_code => Ok(keyword),
+        Some(keyword) if keyword.group_id == 0 => {
+            if permission::is_owner(event.inner.from_uin) {
+                Ok(keyword)
+            } else {
+                Err(UserError::new("全局关键词只有机器人主人可以修改").into())
+            }
+        }
+        _ => Err(UserError::new(format!("本群没有编号为 {} 的关键词", id)).into()),
+    }
+}
+
+// rejected up front, since an invalid regex would only show up in the logs once stored
+fn check_regex(regex: &str) -> anyhow::Result<()> {
+    Regex::new(regex)
+        .map(|_| ())
+        .map_err(|e| UserError::new(format!("正则表达式无效: {}", e)).into())
+}
+
+fn describe(keyword: &Model) -> String {
+    let mut tags = Vec::new();
+    if keyword.group_id == 0 {
+        tags.push("全局");
+    }
+    if keyword.enabled == 0 {
+        tags.push("已停用");
+    }
+    let tags = if tags.is_empty() {
+        String::new()
+    } else {
+        format!("[{}]", tags.join(","))
+    };
+    format!(
+        "{}. {}{} -> {} ({}%)\n",
+        keyword.id.unwrap_or_default(),
+        tags,
+        keyword.regex.as_deref().unwrap_or_default(),
+        keyword.reply.as_deref().unwrap_or_default(),
+        keyword.chance
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::Repos;
+
+    fn keyword(group_id: i64, regex: &str, enabled: i32) -> Model {
+        Model {
+            id: None,
+            group_id,
+            regex: Some(regex.to_string()),
+            reply: Some("reply".to_string()),
+            chance: 100,
+            enabled,
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_follows_the_table() {
+        let repos = Repos::memory();
+        let store = KeywordStore::default();
+        repos.keywords.add(keyword(1, "hello", 1)).await.unwrap();
+        store.reload(&*repos.keywords).await.unwrap();
+        let rules = store.rules();
+        assert_eq!(rules.len(), 1);
+        // nothing changed, the compiled rules are kept
+        store.reload(&*repos.keywords).await.unwrap();
+        assert!(Arc::ptr_eq(&rules, &store.rules()));
+        repos.keywords.add(keyword(0, "(", 1)).await.unwrap();
+        store.reload(&*repos.keywords).await.unwrap();
+        let rules = store.rules();
+        assert_eq!(rules.len(), 2);
+        // an invalid regex is kept, but never fires
+        assert!(rules[1].regex.is_none());
+        assert!(!rules[1].would_fire(1, "("));
+        repos.keywords.remove(1).await.unwrap();
+        store.reload(&*repos.keywords).await.unwrap();
+        assert_eq!(store.rules().len(), 1);
+    }
+
+    #[test]
+    fn would_fire_in_its_group() {
+        let rule = |group_id, enabled| Rule {
+            keyword: keyword(group_id, "^hi", enabled),
+            regex: Some(Regex::new("^hi").unwrap()),
+        };
+        assert!(rule(1, 1).would_fire(1, "hi there"));
+        assert!(!rule(1, 1).would_fire(2, "hi there"));
+        // group 0 answers in every group
+        assert!(rule(0, 1).would_fire(2, "hi there"));
+        assert!(!rule(1, 0).would_fire(1, "hi there"));
+        assert!(!rule(1, 1).would_fire(1, "oh hi"));
+    }
+}
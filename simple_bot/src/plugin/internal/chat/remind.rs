@@ -1,70 +1,172 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
-use async_trait::async_trait;
+use chrono::Utc;
 use proc_qq::re_exports::ricq::msg::MessageChain;
 use proc_qq::re_exports::ricq_core::msg::elem::At;
 use proc_qq::re_exports::ricq_core::msg::MessageChainBuilder;
 use proc_qq::{
-    GroupMessageEvent, MessageChainAppendTrait, MessageEvent, MessageSendToSourceTrait,
-    TextEleParseTrait,
+    MessageChainAppendTrait, MessageEvent, MessageSendToSourceTrait, TextEleParseTrait,
 };
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
 
-use simple_bot_macros::{action, make_action};
+use simple_bot_macros::{action, plugin};
+use tracing::error;
 
-use crate::plugin::{Action, CommandPlugin, Plugin};
+use crate::plugin::{error, PluginContext};
 
-pub struct Remind {
-    actions: Vec<Box<dyn Action>>,
-}
+#[plugin(
+    name = "提醒",
+    desc = "一定时间后做出提醒",
+    on_load = restore_pending,
+    on_shutdown = notify_pending
+)]
+pub struct Remind;
 
-impl Remind {
-    pub fn new() -> Self {
-        Remind {
-            actions: vec![make_action!(remind)],
-        }
-    }
-}
+// the reminders still waiting are saved under this key of the kv store, so they outlive a restart
+const PENDING_KEY: &str = "pending";
 
-impl Plugin for Remind {
-    fn get_name(&self) -> &str {
-        "提醒"
-    }
+// a reminder restored right after a restart waits this long for the login, in seconds
+const MIN_DELAY: i64 = 60;
 
-    fn get_desc(&self) -> &str {
-        "一定时间后做出提醒"
-    }
+#[derive(Clone, Serialize, Deserialize)]
+struct Reminder {
+    group_id: i64,
+    target: i64,
+    content: String,
+    // unix timestamp in seconds
+    due: i64,
 }
 
-#[async_trait]
-impl CommandPlugin for Remind {
-    fn get_actions(&self) -> &Vec<Box<dyn Action>> {
-        &self.actions
-    }
+// reminders still sleeping, keyed by a running number
+#[derive(Default)]
+struct Pending {
+    reminders: Mutex<HashMap<u64, Reminder>>,
+    next_id: AtomicU64,
 }
 
-#[action("{time}分钟后提醒我{content}")]
+/// 一定时间后提醒自己
+#[action("{time:uint}分钟后提醒我{content..}")]
 async fn remind(
     event: &MessageEvent,
-    time: Option<u64>,
-    content: Option<String>,
+    context: &PluginContext,
+    time: u64,
+    content: String,
 ) -> anyhow::Result<bool> {
-    let event = event.as_group_message()?;
-    if time.is_none() || content.is_none() {
-        return Ok(false);
-    }
-    let time = time.unwrap();
-    let content = content.unwrap();
-    let message = build_content(event, format!("我将在{}分钟后提醒你{} ", time, content));
+    let event = error::require_group(event)?;
+    let message = build_content(
+        event.inner.from_uin,
+        format!("我将在{}分钟后提醒你{} ", time, content),
+    );
     event.send_message_to_source(message).await.unwrap();
-    tokio::time::sleep(Duration::from_secs(60 * time)).await;
-    let message = build_content(event, content);
+    let reminder = Reminder {
+        group_id: event.inner.group_code,
+        target: event.inner.from_uin,
+        content,
+        due: Utc::now().timestamp() + 60 * time as i64,
+    };
+    schedule(context, reminder).await?;
+    Ok(true)
+}
+
+/// 一定时间后提醒被@的成员
+#[action("{time:uint}分钟后提醒{target}{content..}")]
+async fn remind_other(
+    event: &MessageEvent,
+    context: &PluginContext,
+    time: u64,
+    target: At,
+    content: String,
+) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    let message = build_content(
+        event.inner.from_uin,
+        format!("我将在{}分钟后提醒{}{} ", time, target.display, content),
+    );
     event.send_message_to_source(message).await.unwrap();
+    let reminder = Reminder {
+        group_id: event.inner.group_code,
+        target: target.target,
+        content,
+        due: Utc::now().timestamp() + 60 * time as i64,
+    };
+    schedule(context, reminder).await?;
     Ok(true)
 }
 
-fn build_content(event: &GroupMessageEvent, content: String) -> MessageChain {
+// save the reminder and wait in the background instead of holding up the message dispatch
+async fn schedule(context: &PluginContext, reminder: Reminder) -> anyhow::Result<()> {
+    let pending = context.state::<Pending>();
+    let id = pending.next_id.fetch_add(1, Ordering::SeqCst);
+    let delay = (reminder.due - Utc::now().timestamp()).max(MIN_DELAY);
+    {
+        let mut reminders = pending.reminders.lock().await;
+        reminders.insert(id, reminder);
+        if let Err(e) = save(context, &reminders).await {
+            reminders.remove(&id);
+            return Err(e);
+        }
+    }
+    let job_context = context.clone();
+    context.scheduler.after(Duration::from_secs(delay as u64), async move {
+        let mut reminders = pending.reminders.lock().await;
+        let Some(reminder) = reminders.remove(&id) else {
+            return Ok(());
+        };
+        save(&job_context, &reminders).await?;
+        drop(reminders);
+        let message = build_content(reminder.target, reminder.content);
+        let sent = job_context
+            .client
+            .send_group_message(reminder.group_id, message)
+            .await;
+        if let Err(e) = sent {
+            error!("发送提醒失败: {:?}", e);
+        }
+        Ok(())
+    });
+    Ok(())
+}
+
+async fn save(context: &PluginContext, reminders: &HashMap<u64, Reminder>) -> anyhow::Result<()> {
+    let reminders = reminders.values().collect::<Vec<_>>();
+    context.kv.set(PENDING_KEY, &reminders).await
+}
+
+// start waiting again for the reminders saved before the last shutdown
+async fn restore_pending(context: &PluginContext) -> anyhow::Result<()> {
+    let saved: Vec<Reminder> = context.kv.get(PENDING_KEY).await?.unwrap_or_default();
+    for reminder in saved {
+        schedule(context, reminder).await?;
+    }
+    Ok(())
+}
+
+// the scheduled jobs die with the bot, so tell everyone waiting that their reminder comes
+// once the bot is back; runs while the client is still connected
+async fn notify_pending(context: &PluginContext) -> anyhow::Result<()> {
+    let reminders = context.state::<Pending>().reminders.lock().await.clone();
+    for reminder in reminders.into_values() {
+        let message = build_content(
+            reminder.target,
+            format!("机器人即将关闭，提醒将在重新启动后发出: {}", reminder.content),
+        );
+        let sent = context
+            .client
+            .send_group_message(reminder.group_id, message)
+            .await;
+        if let Err(e) = sent {
+            error!("发送提醒延后通知失败: {:?}", e);
+        }
+    }
+    Ok(())
+}
+
+fn build_content(target: i64, content: String) -> MessageChain {
     let message = MessageChainBuilder::new().build();
     message
-        .append(At::new(event.inner.from_uin))
+        .append(At::new(target))
         .append(format!(" {}", content).parse_text())
 }
@@ -1,29 +1,49 @@
-use crate::plugin::{Plugin, RawPlugin};
+use crate::plugin::{ConfigSpec, Plugin, PluginContext, PluginRegistration, RawPlugin, Roles};
 use async_trait::async_trait;
 use proc_qq::{
     MessageChainPointTrait, MessageContentTrait, MessageEvent, MessageSendToSourceTrait,
 };
 use rand::prelude::SliceRandom;
-use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::sync::Mutex;
 
 pub struct Repeater {
-    stack: RefCell<VecDeque<String>>,
-    capacity: usize,
+    stack: Mutex<VecDeque<String>>,
 }
 
-unsafe impl Sync for Repeater {}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RepeaterConfig {
+    // how many equal messages in a row are repeated
+    pub capacity: usize,
+}
+
+impl Default for RepeaterConfig {
+    fn default() -> Self {
+        RepeaterConfig { capacity: 3 }
+    }
+}
+
+inventory::submit! {
+    PluginRegistration(|| {
+        let repeater = crate::plugin::instance(Repeater::new());
+        Roles::new(repeater).raw(repeater)
+    })
+}
 
 impl Repeater {
-    pub fn new(capacity: usize) -> Self {
+    pub fn new() -> Self {
         Repeater {
-            stack: RefCell::new(VecDeque::with_capacity(capacity)),
-            capacity,
+            stack: Mutex::new(VecDeque::new()),
         }
     }
 }
 
 impl Plugin for Repeater {
+    fn get_id(&self) -> &str {
+        "Repeater"
+    }
+
     fn get_name(&self) -> &str {
         "复读机"
     }
@@ -31,21 +51,35 @@ impl Plugin for Repeater {
     fn get_desc(&self) -> &str {
         "复读机"
     }
+
+    // only repeat messages that no other plugin has handled
+    fn get_priority(&self) -> i32 {
+        -10
+    }
+
+    fn get_config_spec(&self) -> Option<ConfigSpec> {
+        Some(ConfigSpec::of::<RepeaterConfig>())
+    }
 }
 
 #[async_trait]
 impl RawPlugin for Repeater {
-    async fn on_event(&self, event: &MessageEvent) -> anyhow::Result<bool> {
+    async fn on_event(
+        &self,
+        event: &MessageEvent,
+        context: &PluginContext,
+    ) -> anyhow::Result<bool> {
         let message = event.message_chain();
         let content = message.message_content();
+        let capacity = context.config::<RepeaterConfig>()?.capacity;
         {
-            let stack = self.stack.borrow_mut();
-            if stack.len() <= self.capacity {
+            let stack = self.stack.lock().unwrap();
+            if stack.len() <= capacity {
                 return Ok(false);
             }
         }
         let run = {
-            let mut stack = self.stack.borrow_mut();
+            let mut stack = self.stack.lock().unwrap();
             stack.pop_front();
             stack.push_back(content);
             let iter = stack.iter();
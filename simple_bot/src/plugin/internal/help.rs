@@ -1,62 +1,34 @@
-use crate::plugin::{Action, CommandPlugin, Plugin};
+use crate::plugin::Hook;
 use proc_qq::{MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
-use simple_bot_macros::{action, make_action};
+use simple_bot_macros::{action, plugin};
 
-pub struct Help {
-    actions: Vec<Box<dyn Action>>,
-}
-
-impl Help {
-    pub fn new() -> Self {
-        Help {
-            actions: vec![make_action!(help)],
-        }
-    }
-}
-
-impl Plugin for Help {
-    fn get_name(&self) -> &str {
-        "帮助"
-    }
-
-    fn get_desc(&self) -> &str {
-        "查看所有命令帮助"
-    }
-}
-
-impl CommandPlugin for Help {
-    fn get_actions(&self) -> &Vec<Box<dyn Action>> {
-        &self.actions
-    }
-}
+#[plugin(name = "帮助", desc = "查看所有命令帮助")]
+pub struct Help;
 
 #[action("/help")]
 async fn help(event: &MessageEvent) -> anyhow::Result<bool> {
-    let mut raw_help = String::from(format!("{:=^30}\n", "RAW"));
-    let mut id = 1;
-    for plugin in crate::plugin::RAW_PLUGINS.as_ref() {
-        let name = plugin.get_name();
-        let desc = plugin.get_desc();
-        raw_help.push_str(&format!("{}. {}: {}\n", id, name, desc));
-        id += 1;
-    }
-    event
-        .send_message_to_source(raw_help.parse_message_chain())
-        .await
-        .unwrap();
-    let mut command_help = String::from(format!("{:=^30}\n", "COMMAND"));
+    let mut help = String::from(format!("{:=^30}\n", "HELP"));
     let mut id = 1;
-    for plugin in crate::plugin::COMMAND_PLUGINS.as_ref() {
-        let name = plugin.get_name();
-        let desc = plugin.get_desc();
-        command_help.push_str(&format!("{}. {}: {}\n", id, name, desc));
+    for hook in crate::plugin::DISPATCH_ORDER.iter() {
+        let (kind, actions) = match hook {
+            Hook::Command(plugin) => ("COMMAND", Some(plugin.get_actions())),
+            Hook::Raw(_) => ("RAW", None),
+        };
+        help.push_str(&format!(
+            "{}. [{}] {}({}): {}\n",
+            id,
+            kind,
+            hook.get_name(),
+            hook.get_priority(),
+            hook.get_desc()
+        ));
         id += 1;
-        for action in plugin.get_actions() {
-            command_help.push_str(&format!("{}\n", action.get_pattern()));
+        for action in actions.into_iter().flatten() {
+            help.push_str(&format!("{}\n", action.get_usage()));
         }
     }
     event
-        .send_message_to_source(command_help.parse_message_chain())
+        .send_message_to_source(help.parse_message_chain())
         .await
         .unwrap();
     Ok(true)
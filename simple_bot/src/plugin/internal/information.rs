@@ -1,7 +1,3 @@
 mod div;
 mod guess_meaning;
 mod jikipedia;
-
-pub use div::Div;
-pub use guess_meaning::GuessMeaning;
-pub use jikipedia::Jikipedia;
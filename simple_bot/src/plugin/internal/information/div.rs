@@ -1,7 +1,9 @@
-use crate::plugin::{Action, CommandPlugin, Plugin};
-use crate::model::div::{D1PlayerStats, D2PlayerStats, ProfileDTO, StatsDTO, UbiUser};
+use crate::model::div::{D1PlayerStats, D2PlayerStats, ProfileDTO, StatsDTO};
+use crate::plugin::{PluginContext, ScheduledPlugin};
 use crate::tracing::*;
 
+use std::sync::Arc;
+
 use tokio::{task::JoinSet, sync::Mutex};
 use chrono::{DateTime, Utc};
 use reqwest::{self, header::HeaderValue};
@@ -9,53 +11,61 @@ use thirtyfour::prelude::*;
 use serde_json::{from_str, Value};
 use base64::Engine;
 
-use std::ops::DerefMut;
 use anyhow::anyhow;
-use lazy_static::lazy_static;
 use async_trait::async_trait;
 use proc_qq::{MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
-use simple_bot_macros::{action, make_action};
-
-pub struct Div {
-    actions: Vec<Box<dyn Action>>,
+use simple_bot_macros::{action, plugin};
+
+#[plugin(
+    name = "全境查数据",
+    desc = "查询全境封锁玩家账号数据",
+    config = DivConfig,
+    on_reload = expire_ticket,
+    scheduled
+)]
+pub struct Div;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DivConfig {
+    pub ubi_username: String,
+    pub ubi_password: String,
+    // the port of the chromedriver used to read tracker.gg
+    pub chrome_port: i16,
 }
 
-impl Div {
-    pub fn new() -> Self {
-        Self {
-            actions: vec![make_action!(tracker)],
+impl Default for DivConfig {
+    fn default() -> Self {
+        DivConfig {
+            ubi_username: String::default(),
+            ubi_password: String::default(),
+            chrome_port: 9515,
         }
     }
 }
 
-impl Plugin for Div {
-    fn get_name(&self) -> &str {
-        "全境查数据"
-    }
-
-    fn get_desc(&self) -> &str {
-        "查询全境封锁玩家账号数据"
-    }
-}
-
+// keep the Ubi ticket fresh in the background, so a query does not have to log in first
 #[async_trait]
-impl CommandPlugin for Div {
-    fn get_actions(&self) -> &Vec<Box<dyn Action>> {
-        &self.actions
+impl ScheduledPlugin for Div {
+    fn get_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(4 * 60)
     }
-}
 
-#[action("/div{n} {name}")]
-async fn tracker(event: &MessageEvent, n: Option<String>, name: Option<String>) -> anyhow::Result<bool> {
-    if n.is_none() || name.is_none() {
-        event.send_message_to_source("请输入正确参数 如 /div (1或2) 玩家名".parse_message_chain()).await.unwrap();
-        return Ok(false);
+    async fn on_tick(&self, context: &PluginContext) -> anyhow::Result<()> {
+        if context.config::<DivConfig>()?.ubi_username.is_empty() {
+            return Ok(());
+        }
+        check_expiration_date(context).await
     }
+}
 
-    let msg = match n.unwrap().as_str() {
+#[action("/div{n:1|2} {name} [--json]", user_limit = "1/1m", group_limit = "3/1m")]
+async fn tracker(event: &MessageEvent, context: &PluginContext, n: String, name: String, json: bool) -> anyhow::Result<bool> {
+    let msg = match n.as_str() {
         "1" => {
-            match get_div1_player_stats(&name.unwrap()).await
+            match get_div1_player_stats(context, &name).await
             {
+                Ok(stats) if json => serde_json::to_string_pretty(&stats)?,
                 Ok(stats) => stats.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("\n"),
                 Err(e) => {
                     error!("{:?}", e);
@@ -65,8 +75,9 @@ async fn tracker(event: &MessageEvent, n: Option<String>, name: Option<String>)
             }
         },
         "2" => { 
-            match get_div2_player_stats(&name.unwrap()).await
+            match get_div2_player_stats(context, &name).await
             {
+                Ok(stats) if json => serde_json::to_string_pretty(&stats)?,
                 Ok(stats) => stats.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("\n"),
                 Err(e) => {
                     error!("{:?}", e);
@@ -87,15 +98,38 @@ async fn tracker(event: &MessageEvent, n: Option<String>, name: Option<String>)
 
 // The rest functions are copied from the rust-divtracker-api project
 
-lazy_static! {
-    static ref UBI_TICKET: Mutex<String> = Mutex::new("".to_string());
-    static ref UBI_SESSION_ID: Mutex<String> = Mutex::new("".to_string());
-    static ref UBI_EXPIRATION: Mutex<String> =
-        Mutex::new("2015-11-12T00:00:00.0000000Z".to_string());
+// a time long past, so the ticket is refreshed first
+static EXPIRED: &str = "2015-11-12T00:00:00.0000000Z";
+
+// the Ubi login shared by the queries and the ticks, kept in the context of the plugin
+struct UbiSession {
+    ticket: String,
+    session_id: String,
+    expiration: String,
 }
 
-pub async fn check_expiration_date() -> anyhow::Result<()> {
-    let expiration = UBI_EXPIRATION.lock().await.clone();
+impl Default for UbiSession {
+    fn default() -> Self {
+        UbiSession {
+            ticket: String::new(),
+            session_id: String::new(),
+            expiration: EXPIRED.to_string(),
+        }
+    }
+}
+
+fn ubi_session(context: &PluginContext) -> Arc<Mutex<UbiSession>> {
+    context.state::<Mutex<UbiSession>>()
+}
+
+// the ticket belongs to the old account, log in again on the next query or tick
+async fn expire_ticket(context: &PluginContext) -> anyhow::Result<()> {
+    ubi_session(context).lock().await.expiration = EXPIRED.to_string();
+    Ok(())
+}
+
+pub async fn check_expiration_date(context: &PluginContext) -> anyhow::Result<()> {
+    let expiration = ubi_session(context).lock().await.expiration.clone();
     let mut exp = DateTime::parse_from_rfc3339(&expiration)
         .unwrap()
         .with_timezone(&Utc);
@@ -103,10 +137,10 @@ pub async fn check_expiration_date() -> anyhow::Result<()> {
 
     let mut login_counts = 0;
     while exp < now && login_counts < 5 {
-        login_ubi().await?;
+        login_ubi(context).await?;
         login_counts += 1;
         info!("已刷新 ticket 当前时间：{}", now.to_rfc3339());
-        let expiration = UBI_EXPIRATION.lock().await.clone();
+        let expiration = ubi_session(context).lock().await.expiration.clone();
         exp = DateTime::parse_from_rfc3339(&expiration)
             .unwrap()
             .with_timezone(&Utc);
@@ -119,20 +153,18 @@ pub async fn check_expiration_date() -> anyhow::Result<()> {
 }
 
 pub static UBI_LOGIN_URL: &str = "https://public-ubiservices.ubi.com/v3/profiles/sessions";
-pub async fn login_ubi() -> anyhow::Result<()> {
+pub async fn login_ubi(context: &PluginContext) -> anyhow::Result<()> {
     let mut headers = get_common_header();
 
-    let userpass = format!(
-        "{}:{}",
-        crate::CONFIG.divtrack.ubi_username,
-        crate::CONFIG.divtrack.ubi_password
-    );
+    let config = context.config::<DivConfig>()?;
+    let userpass = format!("{}:{}", config.ubi_username, config.ubi_password);
     let mut auth = String::new();
     base64::engine::general_purpose::STANDARD.encode_string(userpass.as_bytes(), &mut auth);
     headers.insert("Authorization", format!("Basic {}", auth).parse().unwrap());
     auth.clear();
 
-    let resp = reqwest::Client::new()
+    let resp = context
+        .http
         .post(UBI_LOGIN_URL)
         .headers(headers)
         .send()
@@ -145,52 +177,53 @@ pub async fn login_ubi() -> anyhow::Result<()> {
         return Err(anyhow!("登录育碧API失败"));
     }
 
-    let mut ticket = UBI_TICKET.lock().await;
-    *ticket = resp["ticket"].as_str().unwrap().to_string();
-
-    let mut session_id = UBI_SESSION_ID.lock().await;
-    *session_id = resp["sessionId"].as_str().unwrap().to_string();
-
-    let mut expiration = UBI_EXPIRATION.lock().await;
-    *expiration = resp["expiration"].as_str().unwrap().to_string();
+    let session = ubi_session(context);
+    let mut session = session.lock().await;
+    session.ticket = resp["ticket"].as_str().unwrap().to_string();
+    session.session_id = resp["sessionId"].as_str().unwrap().to_string();
+    session.expiration = resp["expiration"].as_str().unwrap().to_string();
 
     Ok(())
 }
 
 pub async fn find_player_id_by_db(
+    context: &PluginContext,
     name: &str,
 ) -> anyhow::Result<Vec<ProfileDTO>> {
-    let mut db = crate::RB.lock().await;
-    let users = UbiUser::select_by_name(db.deref_mut(), name)
+    let ids = context
+        .repos
+        .ubi_users
+        .ids_by_name(name)
         .await
         .map_err(|e| anyhow!("数据库中找不到玩家 {} \n错误: {}", name, e))?;
 
     let mut profiles = vec![];
-    for user in users {
-        profiles.push(ProfileDTO { id: user.id, name: None });
+    for id in ids {
+        profiles.push(ProfileDTO { id, name: None });
     }
     Ok(profiles)
 }
 
 pub async fn find_player_id_by_api(
+    context: &PluginContext,
     name: Option<&str>,
     id: Option<&str>
 ) -> anyhow::Result<Vec<ProfileDTO>> {
     if name.is_none() && id.is_none() {
         return Err(anyhow!("缺少玩家名或UUID"));
     }
-    if let Err(e) = check_expiration_date().await {
+    if let Err(e) = check_expiration_date(context).await {
         return Err(anyhow!(e))
     }
 
-    let ticket = UBI_TICKET.lock().await.clone();
+    let ticket = ubi_session(context).lock().await.ticket.clone();
     let mut headers = get_common_header();
     headers.insert(
         "Authorization",
         format!("Ubi_v1 t={}", &*ticket).parse().unwrap(),
     );
 
-    let session_id = UBI_SESSION_ID.lock().await.clone();
+    let session_id = ubi_session(context).lock().await.session_id.clone();
     headers.insert(
         "Ubi-SessionId",
         (*session_id).parse::<HeaderValue>().unwrap(),
@@ -206,7 +239,8 @@ pub async fn find_player_id_by_api(
         url.push_str(&format!("idOnPlatform={}", id.unwrap()));
     }
 
-    let resp = reqwest::Client::new()
+    let resp = context
+        .http
         .get(&url)
         .headers(headers)
         .send()
@@ -231,12 +265,13 @@ pub async fn find_player_id_by_api(
 }
 
 pub async fn get_player_profiles_by_name(
+    context: &PluginContext,
     name: &str,
     use_db: bool,
 ) -> anyhow::Result<Vec<ProfileDTO>> {
-    let mut profiles = find_player_id_by_api(Some(name), None).await.unwrap_or(vec![]);
+    let mut profiles = find_player_id_by_api(context, Some(name), None).await.unwrap_or(vec![]);
     if use_db {
-        profiles.append(&mut find_player_id_by_db(name).await?);
+        profiles.append(&mut find_player_id_by_db(context, name).await?);
     }
     profiles.sort_by_key(|p| p.id.clone());
     profiles.dedup_by(|a, b| a.id.eq(&b.id));
@@ -244,27 +279,28 @@ pub async fn get_player_profiles_by_name(
 }
 
 pub async fn get_player_stats_by_name(
+    context: &PluginContext,
     name: &str,
     game_space_id: &str,
 ) -> anyhow::Result<Vec<StatsDTO>> {
-    if let Err(e) = check_expiration_date().await {
+    if let Err(e) = check_expiration_date(context).await {
         return Err(anyhow!(e))
     }
 
     let mut headers = get_common_header();
-    let ticket = UBI_TICKET.lock().await.clone();
+    let ticket = ubi_session(context).lock().await.ticket.clone();
     headers.insert(
         "Authorization",
         format!("Ubi_v1 t={}", &ticket).parse().unwrap(),
     );
 
-    let session_id = UBI_SESSION_ID.lock().await.clone();
+    let session_id = ubi_session(context).lock().await.session_id.clone();
     headers.insert(
         "Ubi-SessionId",
         (*session_id).parse::<HeaderValue>().unwrap(),
     );
 
-    let mut profiles = get_player_profiles_by_name(name, true).await?;
+    let mut profiles = get_player_profiles_by_name(context, name, true).await?;
 
     let mut results: Vec<StatsDTO> = vec![];
     let urls = profiles
@@ -278,10 +314,9 @@ pub async fn get_player_stats_by_name(
         })
         .collect::<Vec<String>>();
 
-    let client = reqwest::Client::new();
     let mut handles = JoinSet::new();
     for url in &urls {
-        handles.spawn(client.get(&*url).headers(headers.clone()).send());
+        handles.spawn(context.http.get(&*url).headers(headers.clone()).send());
     }
 
     let mut i = 0;
@@ -302,7 +337,8 @@ pub async fn get_player_stats_by_name(
                     "https://public-ubiservices.ubi.com/v2/profiles?userId={}&platformType=uplay",
                     &profile.id
                 );
-                let res = reqwest::Client::new()
+                let res = context
+                    .http
                     .get(&url)
                     .headers(headers.clone())
                     .send()
@@ -317,7 +353,7 @@ pub async fn get_player_stats_by_name(
             }
         };
 
-        match UbiUser::store_user_name(&profile.id, &name).await {
+        match context.repos.ubi_users.store_name(&profile.id, &name).await {
             Ok(_) => info!("Stored name {} for user {}", &name, &profile.id),
             Err(e) => {
                 warn!(
@@ -341,12 +377,14 @@ pub async fn get_player_stats_by_name(
 
 pub static DIV1_SPACE_ID: &str = "6edd234a-abff-4e90-9aab-b9b9c6e49ff7";
 pub async fn get_div1_player_stats(
+    context: &PluginContext,
     name: &str,
 ) -> anyhow::Result<Vec<D1PlayerStats>> {
-    let res = get_player_stats_by_name(name, DIV1_SPACE_ID).await?;
+    let res = get_player_stats_by_name(context, name, DIV1_SPACE_ID).await?;
     let mut handles = JoinSet::new();
     let mut results: Vec<D1PlayerStats> = vec![];
     for r in res.into_iter() {
+        let context = context.clone();
         handles.spawn(async move {
             let p = r.profile;
             let s = r.stats;
@@ -367,7 +405,7 @@ pub async fn get_div1_player_stats(
                 skill_kills: s[7]["value"].as_str().unwrap().parse::<u64>().unwrap_or(0),
                 total_kills: s[8]["value"].as_str().unwrap().parse::<u64>().unwrap_or(0),
                 gear_score: s[11]["value"].as_str().unwrap().parse::<u64>().unwrap_or(0),
-                all_names: UbiUser::get_user_names_by_id(p.id.clone().as_str())
+                all_names: context.repos.ubi_users.names_by_id(p.id.clone().as_str())
                     .await
                     .unwrap_or(vec![]),
             }
@@ -387,27 +425,30 @@ pub async fn get_div1_player_stats(
 // pub static DIV2_SPACE_ID: &str = "60859c37-949d-49e2-8fc8-6d8dc40f1a9e";
 pub static TRACKER_URL: &str = "https://api.tracker.gg/api/v2/division-2/standard/profile/uplay/";
 pub async fn get_div2_player_stats(
+    context: &PluginContext,
     name: &str,
 ) -> anyhow::Result<Vec<D2PlayerStats>> {
-    let mut profiles = find_player_id_by_api(Some(name), None).await.unwrap_or(vec![]);
+    let mut profiles = find_player_id_by_api(context, Some(name), None).await.unwrap_or(vec![]);
 
     if profiles.is_empty() {
-        profiles = find_player_id_by_db(&name).await?;
+        profiles = find_player_id_by_db(context, &name).await?;
         if profiles.is_empty() {
             return Err(anyhow!("API/数据库均找不到玩家 {}", name));
         }
         for profile in profiles.iter_mut() {
-            profile.name = find_player_id_by_api(None, Some(&profile.id)).await?[0].name.clone();
+            profile.name = find_player_id_by_api(context, None, Some(&profile.id)).await?[0].name.clone();
         }
     }
 
     let mut handles = JoinSet::new();
     let mut results: Vec<D2PlayerStats> = vec![];
+    let chrome_port = context.config::<DivConfig>()?.chrome_port;
 
     for p in profiles {
+        let context = context.clone();
         handles.spawn(async move {
             let p_name = p.name.clone().unwrap_or("".to_string());
-            match UbiUser::store_user_name(&p.id, &p_name).await {
+            match context.repos.ubi_users.store_name(&p.id, &p_name).await {
                 Ok(_) => info!("Stored name {} for user {}", &p_name, &p.id),
                 Err(e) => {
                     warn!(
@@ -417,7 +458,7 @@ pub async fn get_div2_player_stats(
                 }
             }
         
-            let driver = get_webdriver().await.unwrap();
+            let driver = get_webdriver(chrome_port).await.unwrap();
             driver.goto(format!("{}{}", TRACKER_URL, p.name.clone().unwrap_or("".to_string()))).await.unwrap();
             let data = driver.find(By::Css("body")).await.unwrap().text().await.unwrap();
             driver.quit().await.unwrap();
@@ -457,7 +498,7 @@ pub async fn get_div2_player_stats(
                 longest_rogue: stats["timePlayedRogueLongest"]["value"].as_u64().unwrap_or(0) / 60,
                 conflict_rank: stats["latestConflictRank"]["value"].as_u64().unwrap_or(0),
                 conflict_playtime: stats["timePlayedConflict"]["value"].as_u64().unwrap_or(0) / 3600,
-                all_names: UbiUser::get_user_names_by_id(p.id.clone().as_str())
+                all_names: context.repos.ubi_users.names_by_id(p.id.clone().as_str())
                     .await
                     .unwrap_or(vec![])
             }
@@ -508,7 +549,7 @@ pub fn get_common_header() -> header::HeaderMap {
     headers
 }
 
-pub async fn get_webdriver() -> WebDriverResult<WebDriver> {
+pub async fn get_webdriver(port: i16) -> WebDriverResult<WebDriver> {
     let mut caps = DesiredCapabilities::chrome();
 
     let _ = caps.set_disable_web_security();
@@ -521,7 +562,6 @@ pub async fn get_webdriver() -> WebDriverResult<WebDriver> {
     let _ = caps.add_chrome_arg("--window-size=400,300");
     let _ = caps.add_chrome_option("detach", true);
 
-    let port = crate::CONFIG.divtrack.chrome_port;
     let driver = WebDriver::new(format!("{}{}", "http://localhost:", port).as_str(), caps).await?;
     Ok(driver)
 }
\ No newline at end of file
@@ -1,46 +1,21 @@
-use crate::plugin::{Action, CommandPlugin, Plugin};
-use async_trait::async_trait;
 use proc_qq::{MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
-use simple_bot_macros::{action, make_action};
+use simple_bot_macros::{action, plugin};
 use std::collections::HashMap;
 
-pub struct GuessMeaning {
-    actions: Vec<Box<dyn Action>>,
-}
+use crate::plugin::PluginContext;
 
-impl GuessMeaning {
-    pub fn new() -> Self {
-        Self {
-            actions: vec![make_action!(search)],
-        }
-    }
-}
+#[plugin(name = "能不能好好说话", desc = "按拼音首字母猜意思")]
+pub struct GuessMeaning;
 
-impl Plugin for GuessMeaning {
-    fn get_name(&self) -> &str {
-        "能不能好好说话"
-    }
-
-    fn get_desc(&self) -> &str {
-        "按拼音首字母猜意思"
-    }
-}
-
-#[async_trait]
-impl CommandPlugin for GuessMeaning {
-    fn get_actions(&self) -> &Vec<Box<dyn Action>> {
-        &self.actions
-    }
-}
-
-#[action("/nbnhhsh {content}")]
-async fn search(event: &MessageEvent, content: Option<String>) -> anyhow::Result<bool> {
-    if content.is_none() {
-        return Ok(false);
-    }
+#[action("/nbnhhsh {content..}")]
+async fn search(
+    event: &MessageEvent,
+    context: &PluginContext,
+    content: String,
+) -> anyhow::Result<bool> {
     let url = "https://lab.magiconch.com/api/nbnhhsh/guess";
-    let mut request = reqwest::ClientBuilder::new().build().unwrap().post(url);
-    request = request.json(&Into::<HashMap<_, _>>::into([("text", content.unwrap())]));
+    let mut request = context.http.post(url);
+    request = request.json(&Into::<HashMap<_, _>>::into([("text", content)]));
     let resp = request.send().await?;
     let text = resp.text().await?;
     let content = parse_content(&text);
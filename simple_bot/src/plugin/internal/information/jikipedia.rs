@@ -1,48 +1,23 @@
-use crate::plugin::{Action, CommandPlugin, Plugin};
-use async_trait::async_trait;
 use proc_qq::{MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
-use simple_bot_macros::{action, make_action};
+use simple_bot_macros::{action, plugin};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 
-pub struct Jikipedia {
-    actions: Vec<Box<dyn Action>>,
-}
-
-impl Jikipedia {
-    pub fn new() -> Self {
-        Self {
-            actions: vec![make_action!(search)],
-        }
-    }
-}
-
-impl Plugin for Jikipedia {
-    fn get_name(&self) -> &str {
-        "小鸡词典"
-    }
+use crate::plugin::PluginContext;
 
-    fn get_desc(&self) -> &str {
-        "可以查梗的网络词典"
-    }
-}
+#[plugin(name = "小鸡词典", desc = "可以查梗的网络词典")]
+pub struct Jikipedia;
 
-#[async_trait]
-impl CommandPlugin for Jikipedia {
-    fn get_actions(&self) -> &Vec<Box<dyn Action>> {
-        &self.actions
-    }
-}
-
-#[action("/查梗 {content}")]
-async fn search(event: &MessageEvent, content: Option<String>) -> anyhow::Result<bool> {
-    if content.is_none() {
-        return Ok(false);
-    }
+#[action("/查梗 {content..}")]
+async fn search(
+    event: &MessageEvent,
+    context: &PluginContext,
+    content: String,
+) -> anyhow::Result<bool> {
     let url = "https://api.jikipedia.com/go/auto_complete";
-    let mut request = reqwest::ClientBuilder::new().build().unwrap().post(url);
+    let mut request = context.http.post(url);
     request = request.header("Client", "Web");
-    request = request.json(&Into::<HashMap<_, _>>::into([("phrase", content.unwrap())]));
+    request = request.json(&Into::<HashMap<_, _>>::into([("phrase", content)]));
     let resp = request.send().await?;
     let text = resp.text().await?;
     let content = parse_content(&text);
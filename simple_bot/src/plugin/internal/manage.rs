@@ -0,0 +1,3 @@
+mod blacklist;
+mod reload;
+mod switch;
@@ -0,0 +1,59 @@
+use proc_qq::{MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
+use simple_bot_macros::{action, plugin};
+
+use crate::plugin::{error, permission};
+
+#[plugin(name = "黑名单管理", desc = "禁止群成员使用机器人命令", root = "/blacklist")]
+pub struct BlacklistManager;
+
+/// 将成员加入本群黑名单
+#[action("/blacklist add {user}", role = GroupAdmin)]
+async fn add_blacklist(event: &MessageEvent, user: i64) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    if permission::is_owner(user) {
+        event
+            .send_message_to_source("不能将机器人主人加入黑名单".parse_message_chain())
+            .await
+            .unwrap();
+        return Ok(false);
+    }
+    permission::set_blacklisted(event.inner.group_code, user, true).await?;
+    event
+        .send_message_to_source(format!("已将 {} 加入黑名单", user).parse_message_chain())
+        .await
+        .unwrap();
+    Ok(true)
+}
+
+/// 将成员移出本群黑名单
+#[action("/blacklist del {user}", role = GroupAdmin)]
+async fn del_blacklist(event: &MessageEvent, user: i64) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    permission::set_blacklisted(event.inner.group_code, user, false).await?;
+    event
+        .send_message_to_source(format!("已将 {} 移出黑名单", user).parse_message_chain())
+        .await
+        .unwrap();
+    Ok(true)
+}
+
+/// 查看本群黑名单
+#[action("/blacklist list", role = GroupAdmin)]
+async fn list_blacklist(event: &MessageEvent) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    let users = permission::get_blacklist(event.inner.group_code).await;
+    let text = if users.is_empty() {
+        "黑名单为空".to_string()
+    } else {
+        let mut text = String::from(format!("{:=^30}\n", "BLACKLIST"));
+        for user in users {
+            text.push_str(&format!("{}\n", user));
+        }
+        text
+    };
+    event
+        .send_message_to_source(text.parse_message_chain())
+        .await
+        .unwrap();
+    Ok(true)
+}
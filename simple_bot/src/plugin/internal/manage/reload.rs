@@ -0,0 +1,21 @@
+use proc_qq::{MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
+use simple_bot_macros::{action, plugin};
+
+use crate::plugin::reload;
+
+#[plugin(name = "配置重载", desc = "不重启机器人重新读取配置文件")]
+pub struct ConfigReloader;
+
+/// 重新读取配置文件，配置无效时继续使用原配置
+#[action("/reload", role = Owner)]
+async fn reload_config(event: &MessageEvent) -> anyhow::Result<bool> {
+    let text = match reload::reload().await {
+        Ok(_) => "配置已重新加载".to_string(),
+        Err(e) => format!("配置无效，继续使用原配置\n错误: {:#}", e),
+    };
+    event
+        .send_message_to_source(text.parse_message_chain())
+        .await
+        .unwrap();
+    Ok(true)
+}
@@ -0,0 +1,81 @@
+use proc_qq::{MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
+use simple_bot_macros::{action, plugin};
+
+use crate::plugin::{error, switch};
+
+#[plugin(name = "插件管理", desc = "按群开启或关闭插件", root = "/plugin")]
+pub struct PluginManager;
+
+/// 在本群开启插件
+#[action("/plugin on {name}", role = GroupAdmin)]
+async fn enable_plugin(event: &MessageEvent, name: String) -> anyhow::Result<bool> {
+    switch_plugin(event, name, true).await
+}
+
+/// 在本群关闭插件
+#[action("/plugin off {name}", role = GroupAdmin)]
+async fn disable_plugin(event: &MessageEvent, name: String) -> anyhow::Result<bool> {
+    switch_plugin(event, name, false).await
+}
+
+/// 查看本群的插件开关
+#[action("/plugin list")]
+async fn list_plugins(event: &MessageEvent) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    let group_id = event.inner.group_code;
+    let mut text = String::from(format!("{:=^30}\n", "PLUGIN"));
+    for (i, (id, name)) in plugin_entries().into_iter().enumerate() {
+        let state = if switch::is_enabled_in_group(group_id, id).await {
+            "开启"
+        } else {
+            "关闭"
+        };
+        text.push_str(&format!("{}. {}({}): {}\n", i + 1, id, name, state));
+    }
+    event
+        .send_message_to_source(text.parse_message_chain())
+        .await
+        .unwrap();
+    Ok(true)
+}
+
+async fn switch_plugin(
+    event: &MessageEvent,
+    name: String,
+    enabled: bool,
+) -> anyhow::Result<bool> {
+    let event = error::require_group(event)?;
+    let name = name.trim();
+    let id = plugin_entries()
+        .into_iter()
+        .find(|(id, plugin_name)| id.eq_ignore_ascii_case(name) || *plugin_name == name)
+        .map(|(id, _)| id);
+    let id = match id {
+        // the manager itself must stay reachable, otherwise it could never be turned back on
+        Some(id) if id != "PluginManager" => id,
+        _ => {
+            event
+                .send_message_to_source(format!("找不到插件: {}", name).parse_message_chain())
+                .await
+                .unwrap();
+            return Ok(false);
+        }
+    };
+    switch::set_enabled(event.inner.group_code, id, enabled).await?;
+    event
+        .send_message_to_source(
+            format!("已{}插件 {}", if enabled { "开启" } else { "关闭" }, id)
+                .parse_message_chain(),
+        )
+        .await
+        .unwrap();
+    Ok(true)
+}
+
+// (id, name) of every registered plugin
+fn plugin_entries() -> Vec<(&'static str, &'static str)> {
+    crate::plugin::PLUGINS
+        .iter()
+        .map(|roles| (roles.plugin.get_id(), roles.plugin.get_name()))
+        .collect()
+}
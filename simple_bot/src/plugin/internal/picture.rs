@@ -3,6 +3,3 @@ mod pixiv_proxy;
 mod saucenao;
 mod search;
 mod search_source;
-
-pub use pixiv_proxy::PixivProxy;
-pub use search::Search;
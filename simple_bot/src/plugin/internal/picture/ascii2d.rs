@@ -16,11 +16,11 @@ impl SearchSource for Ascii2d {
 
     async fn search(
         &self,
+        http: &reqwest::Client,
         url: String,
     ) -> anyhow::Result<Vec<Box<dyn PictureResult + Send + Sync>>> {
         let mut result: Vec<Box<dyn PictureResult + Send + Sync>> = Vec::new();
-        let client = reqwest::ClientBuilder::new().build().unwrap();
-        let resp = client
+        let resp = http
             .get(format!("{}{}", API.clone(), &url))
             .header("User-Agent", "PostmanRuntime/7.29.2")
             .send()
@@ -2,9 +2,7 @@ use std::collections::HashSet;
 use std::ops::SubAssign;
 use std::time::Duration;
 
-use async_trait::async_trait;
 use chrono::prelude::*;
-use lazy_static::lazy_static;
 use proc_qq::re_exports::ricq::msg::MessageChain;
 use proc_qq::re_exports::ricq_core::msg::MessageChainBuilder;
 use proc_qq::{
@@ -12,47 +10,31 @@ use proc_qq::{
     MessageSendToSourceTrait, TextEleParseTrait,
 };
 
-use simple_bot_macros::{action, make_action};
+use simple_bot_macros::{action, plugin};
 
-use crate::plugin::{Action, CommandPlugin, Plugin};
+use crate::plugin::PluginContext;
 
-pub struct PixivProxy {
-    actions: Vec<Box<dyn Action>>,
-}
+#[plugin(name = "Pixiv图片获取", desc = "通过反代理Pixiv获取到作品的信息", config = PixivProxyConfig)]
+pub struct PixivProxy;
 
-impl PixivProxy {
-    pub fn new() -> Self {
-        PixivProxy {
-            actions: vec![make_action!(get)],
-        }
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PixivProxyConfig {
+    // how long an R18 work stays in the chat before it is recalled, in seconds
+    pub recall_secs: u64,
 }
 
-impl Plugin for PixivProxy {
-    fn get_name(&self) -> &str {
-        "Pixiv图片获取"
-    }
-
-    fn get_desc(&self) -> &str {
-        "通过反代理Pixiv获取到作品的信息"
+impl Default for PixivProxyConfig {
+    fn default() -> Self {
+        PixivProxyConfig { recall_secs: 5 }
     }
 }
 
-#[async_trait]
-impl CommandPlugin for PixivProxy {
-    fn get_actions(&self) -> &Vec<Box<dyn Action>> {
-        &self.actions
-    }
-}
-
-#[action("/pixiv work {id}")]
-async fn get(event: &MessageEvent, id: Option<String>) -> anyhow::Result<bool> {
-    if id.is_none() {
-        return Ok(false);
-    }
-    let id = id.unwrap();
+#[action("/pixiv work {id}", user_limit = "1/15s")]
+async fn get(event: &MessageEvent, context: &PluginContext, id: u64) -> anyhow::Result<bool> {
     let api = format!("https://api.obfs.dev/api/pixiv/illust?id={id}");
-    let resp = reqwest::Client::new()
+    let resp = context
+        .http
         .get(&api)
         .header("Referer", "https://www.pixiv.net/")
         .header("User-Agent", "PixivAndroidApp/5.0.234 (Android 6.0)")
@@ -85,7 +67,7 @@ async fn get(event: &MessageEvent, id: Option<String>) -> anyhow::Result<bool> {
     let title = illustration["title"].as_str().unwrap_or("");
     let user_name = illustration["user"]["name"].as_str().unwrap_or("");
     let account = illustration["user"]["account"].as_str().unwrap_or("");
-    let mut time = DateTime::parse_from_rfc3339(illustration["create_date"].as_str().unwrap())?;
+    let mut time = DateTime::parse_from_rfc3339(illustration["create_date"].as_str().unwrap_or_default())?;
     let format = format!(
         "%Y年%m月%d日{}%H点%M分",
         if time.hour() <= 12 {
@@ -100,7 +82,7 @@ async fn get(event: &MessageEvent, id: Option<String>) -> anyhow::Result<bool> {
     );
     let time_text = time.format(&format).to_string();
     let tags = flatten_tags(&illustration["tags"]);
-    let r18 = validate(&tags);
+    let r18 = validate(&tags, &context.state::<R18Pattern>());
     let count = illustration["page_count"].as_u64().unwrap_or(0);
     let view = illustration["total_view"].as_u64().unwrap_or(0);
     let mark = illustration["total_bookmarks"].as_u64().unwrap_or(0);
@@ -115,7 +97,7 @@ https://www.pixiv.net/artworks/{id}
         .parse_text(),
     );
     if count == 1 {
-        builder = upload_image(event, builder, &id).await.unwrap();
+        builder = upload_image(event, &context.http, builder, &id.to_string()).await?;
     } else {
         builder = builder.append(
             format!(
@@ -125,9 +107,7 @@ https://www.pixiv.net/artworks/{id}
             .parse_text(),
         );
         for i in 1..=count {
-            builder = upload_image(event, builder, &format!("{id}-{i}"))
-                .await
-                .unwrap();
+            builder = upload_image(event, &context.http, builder, &format!("{id}-{i}")).await?;
         }
     }
     builder = builder.append(
@@ -140,17 +120,18 @@ https://www.pixiv.net/artworks/{id}
 R18: {r18}
 直连链接: https://pixiv.re/{id}.jpg",
             id = if count == 1 {
-                id
+                id.to_string()
             } else {
                 format!("{}{{1-{}}}", id, count)
             }
         )
         .parse_text(),
     );
-    let receipt = event.send_message_to_source(builder).await.unwrap();
+    let receipt = event.send_message_to_source(builder).await?;
     if r18 {
-        tokio::time::sleep(Duration::from_secs(5)).await;
-        event.recall(receipt).await.unwrap();
+        let delay = context.config::<PixivProxyConfig>()?.recall_secs;
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+        event.recall(receipt).await?;
     }
     Ok(true)
 }
@@ -171,20 +152,28 @@ fn flatten_tags(tags: &serde_json::Value) -> HashSet<&str> {
     }
     set
 }
-lazy_static! {
-    static ref R18_PATTERN: regex::Regex = regex::Regex::new("R-[1-9]+").unwrap();
+// the tags marking an R18 work, compiled once and kept in the context
+struct R18Pattern(regex::Regex);
+
+impl Default for R18Pattern {
+    fn default() -> Self {
+        R18Pattern(regex::Regex::new("R-[1-9]+").unwrap())
+    }
 }
 
-fn validate(tags: &HashSet<&str>) -> bool {
-    tags.iter().any(|tag| R18_PATTERN.is_match(tag))
+fn validate(tags: &HashSet<&str>, pattern: &R18Pattern) -> bool {
+    tags.iter().any(|tag| pattern.0.is_match(tag))
 }
 
 async fn upload_image(
     event: &MessageEvent,
+    http: &reqwest::Client,
     mut builder: MessageChain,
     image_id: &String,
 ) -> anyhow::Result<MessageChain> {
-    let bytes = &reqwest::get(format!("https://pixiv.re/{image_id}.jpg"))
+    let bytes = &http
+        .get(format!("https://pixiv.re/{image_id}.jpg"))
+        .send()
         .await?
         .bytes()
         .await?;
@@ -1,14 +1,21 @@
 use crate::plugin::internal::picture::search_source::{PictureResult, SearchSource};
-use lazy_static::lazy_static;
 use proc_qq::re_exports::async_trait::async_trait;
 
-pub struct SauceNao;
+pub struct SauceNao {
+    api: String,
+    min_similarity: f64,
+}
 
-lazy_static! {
-    static ref API: String = format!(
-        "https://saucenao.com/search.php?db=999&output_type=2&testmode=1&api_key={}&numres=16&url=",
-        crate::CONFIG.saucenao.api_key
-    );
+impl SauceNao {
+    pub fn new(api_key: &str, min_similarity: f64) -> Self {
+        SauceNao {
+            api: format!(
+                "https://saucenao.com/search.php?db=999&output_type=2&testmode=1&api_key={}&numres=16&url=",
+                api_key
+            ),
+            min_similarity,
+        }
+    }
 }
 
 #[async_trait]
@@ -19,10 +26,14 @@ impl SearchSource for SauceNao {
 
     async fn search(
         &self,
+        http: &reqwest::Client,
         url: String,
     ) -> anyhow::Result<Vec<Box<dyn PictureResult + Send + Sync>>> {
         let mut result: Vec<Box<dyn PictureResult + Send + Sync>> = Vec::new();
-        let resp = reqwest::get(format!("{}{}", API.clone(), &url)).await?;
+        let resp = http
+            .get(format!("{}{}", self.api, &url))
+            .send()
+            .await?;
         let d = &resp.text().await?;
         let value = serde_json::from_str::<serde_json::Value>(d)?;
         let results = value["results"].as_array();
@@ -37,7 +48,7 @@ impl SearchSource for SauceNao {
                 continue;
             }
             let similarity = similarity.unwrap().parse::<f64>().unwrap();
-            if similarity < 57.5 {
+            if similarity < self.min_similarity {
                 continue;
             }
             let mut ext_urls_list = Vec::new();
@@ -1,145 +1,142 @@
 use std::time::Duration;
 
-use async_trait::async_trait;
-use proc_qq::re_exports::ricq::msg::elem::RQElem;
+use proc_qq::re_exports::ricq::msg::elem::{GroupImage, RQElem};
 use proc_qq::re_exports::ricq::msg::MessageChain;
 use proc_qq::{MessageChainParseTrait, MessageEvent, MessageSendToSourceTrait};
 
-use simple_bot_macros::action;
-use simple_bot_macros::make_action;
+use simple_bot_macros::{action, plugin};
 
 use crate::future::WaitForMessage;
+use crate::plugin::PluginContext;
 use crate::plugin::internal::picture::ascii2d::Ascii2d;
 use crate::plugin::internal::picture::saucenao::SauceNao;
 use crate::plugin::internal::picture::search_source::SearchSource;
-use crate::plugin::Plugin;
-use crate::plugin::{Action, CommandPlugin};
 
-pub struct Search {
-    actions: Vec<Box<dyn Action>>,
-}
-
-unsafe impl Send for Search {}
-unsafe impl Sync for Search {}
-
-impl Plugin for Search {
-    fn get_name(&self) -> &str {
-        "二次元图片搜索"
-    }
-
-    fn get_desc(&self) -> &str {
-        ""
-    }
-}
+#[plugin(name = "二次元图片搜索", config = SearchConfig)]
+pub struct Search;
 
-#[async_trait]
-impl CommandPlugin for Search {
-    fn get_actions(&self) -> &Vec<Box<dyn Action>> {
-        &self.actions
-    }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    pub saucenao_api_key: String,
+    // SauceNao results less similar than this are dropped
+    pub min_similarity: f64,
 }
 
-impl Search {
-    pub fn new() -> Self {
-        Self {
-            actions: vec![make_action!(search)],
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            saucenao_api_key: String::default(),
+            min_similarity: 57.5,
         }
     }
 }
 
-#[action("/搜图 {source_type}")]
-async fn search(event: &MessageEvent, source_type: Option<usize>) -> anyhow::Result<bool> {
-    if source_type.is_none() {
-        return Ok(false);
-    }
-    let source_type = source_type.unwrap();
-    let search_source = select_source(source_type);
+/// 以图搜图，source_type 为 1 时使用 SauceNao，为 2 时使用 Ascii2d，没有附带图片时会等待发送
+#[action(
+    "/搜图 {source_type} {image} [--all] [--min-sim=<f64>]",
+    user_limit = "1/30s",
+    group_limit = "5/1m"
+)]
+async fn search(
+    event: &MessageEvent,
+    context: &PluginContext,
+    source_type: usize,
+    image: Option<GroupImage>,
+    all: bool,
+    min_sim: Option<f64>,
+) -> anyhow::Result<bool> {
+    let search_source = select_source(source_type, &context.config::<SearchConfig>()?);
     if search_source.is_none() {
         return Ok(false);
     }
     let search_source = search_source.unwrap();
+    let image = match image {
+        Some(image) => Some(image),
+        None => wait_for_image(event).await,
+    };
+    let url = match image {
+        Some(image) => image.url(),
+        None => {
+            event
+                .send_message_to_source("未接收到图片消息，放弃搜索！".parse_message_chain())
+                .await
+                .unwrap();
+            return Ok(false);
+        }
+    };
     event
-        .send_message_to_source("请在五秒内发送图片！".parse_message_chain())
+        .send_message_to_source(
+            format!("{}搜索中，请稍后...", search_source.get_name()).parse_message_chain(),
+        )
         .await
         .unwrap();
-    let image = event.wait_for_message(Duration::from_secs(5)).await;
-    if image.is_none() {
-        event
-            .send_message_to_source("未接收到图片消息，放弃搜索！".parse_message_chain())
-            .await
-            .unwrap();
-        return Ok(false);
-    }
-    for element in image.unwrap().into_iter() {
-        match element {
-            RQElem::GroupImage(image) => {
-                let url = image.url();
+    let res = search_source.search(&context.http, url).await;
+    match res {
+        Ok(res) => {
+            // results without a similarity are never filtered out
+            let res = res
+                .into_iter()
+                .filter(|result| {
+                    let similarity = result.get_similarity();
+                    similarity < 0.0 || !min_sim.is_some_and(|min| similarity < min)
+                })
+                .take(if all { usize::MAX } else { 1 })
+                .collect::<Vec<_>>();
+            if res.len() == 0 {
                 event
-                    .send_message_to_source(
-                        format!("{}搜索中，请稍后...", search_source.get_name())
-                            .parse_message_chain(),
-                    )
+                    .send_message_to_source("未搜索到结果".parse_message_chain())
                     .await
                     .unwrap();
-                let res = search_source.search(url).await;
-                match res {
-                    Ok(res) => {
-                        if res.len() == 0 {
-                            event
-                                .send_message_to_source("未搜索到结果".parse_message_chain())
-                                .await
-                                .unwrap();
-                            return Ok(true);
-                        }
-                        for result in res {
-                            let thumbnail = result.get_thumbnail();
-                            let u8 = reqwest::get(thumbnail)
-                                .await
-                                .ok()
-                                .unwrap()
-                                .bytes()
-                                .await
-                                .unwrap();
-                            let mut message_chain = MessageChain::default();
-                            let image = event.upload_image_to_source(u8).await.unwrap();
-                            message_chain.push(image);
-                            let mut builder = format!(
-                                "相似度: {}\n画师名: {}\n相关链接:\n",
-                                result.get_similarity(),
-                                result.get_member_name()
-                            );
-                            for url in result.get_ext_urls() {
-                                builder.push_str(format!("{}\n", url).as_str());
-                            }
-                            message_chain.push(
-                                proc_qq::re_exports::ricq_core::msg::elem::Text::new(builder),
-                            );
-                            event.send_message_to_source(message_chain).await.unwrap();
-                            return Ok(true);
-                        }
-                    }
-                    Err(err) => {
-                        event
-                            .send_message_to_source(err.to_string().parse_message_chain())
-                            .await
-                            .unwrap();
-                        return Ok(false);
-                    }
+                return Ok(true);
+            }
+            for result in res {
+                let thumbnail = result.get_thumbnail();
+                let u8 = context.http.get(thumbnail).send().await?.bytes().await?;
+                let mut message_chain = MessageChain::default();
+                let image = event.upload_image_to_source(u8).await?;
+                message_chain.push(image);
+                let mut builder = format!(
+                    "相似度: {}\n画师名: {}\n相关链接:\n",
+                    result.get_similarity(),
+                    result.get_member_name()
+                );
+                for url in result.get_ext_urls() {
+                    builder.push_str(format!("{}\n", url).as_str());
                 }
+                message_chain.push(proc_qq::re_exports::ricq_core::msg::elem::Text::new(builder));
+                event.send_message_to_source(message_chain).await?;
             }
-            _ => {}
+            Ok(true)
+        }
+        Err(err) => {
+            event
+                .send_message_to_source(err.to_string().parse_message_chain())
+                .await
+                .unwrap();
+            Ok(false)
         }
     }
+}
+
+async fn wait_for_image(event: &MessageEvent) -> Option<GroupImage> {
     event
-        .send_message_to_source("未接收到图片消息，放弃搜索！".parse_message_chain())
+        .send_message_to_source("请在五秒内发送图片！".parse_message_chain())
         .await
         .unwrap();
-    Ok(false)
+    let message = event.wait_for_message(Duration::from_secs(5)).await?;
+    message.into_iter().find_map(|element| match element {
+        RQElem::GroupImage(image) => Some(image),
+        _ => None,
+    })
 }
 
-fn select_source(source_type: usize) -> Option<Box<dyn SearchSource>> {
+fn select_source(source_type: usize, config: &SearchConfig) -> Option<Box<dyn SearchSource>> {
     match source_type {
-        1 => Some(Box::new(SauceNao)),
+        1 => Some(Box::new(SauceNao::new(
+            &config.saucenao_api_key,
+            config.min_similarity,
+        ))),
         2 => Some(Box::new(Ascii2d)),
         _ => None,
     }
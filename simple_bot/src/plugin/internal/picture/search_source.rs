@@ -6,6 +6,7 @@ pub trait SearchSource: Send + Sync {
 
     async fn search(
         &self,
+        http: &reqwest::Client,
         url: String,
     ) -> anyhow::Result<Vec<Box<dyn PictureResult + Send + Sync>>>;
 }
@@ -1,3 +1 @@
 mod bilibili;
-
-pub use bilibili::BilibiliVideo;
@@ -1,4 +1,4 @@
-use crate::plugin::{Plugin, RawPlugin};
+use crate::plugin::{Plugin, PluginContext, PluginRegistration, RawPlugin, Roles};
 use proc_qq::re_exports::async_trait::async_trait;
 use proc_qq::re_exports::ricq_core::msg::MessageChainBuilder;
 use proc_qq::*;
@@ -12,6 +12,13 @@ pub struct BilibiliVideo {
     client: reqwest::Client,
 }
 
+inventory::submit! {
+    PluginRegistration(|| {
+        let video = crate::plugin::instance(BilibiliVideo::new());
+        Roles::new(video).raw(video)
+    })
+}
+
 impl BilibiliVideo {
     pub fn new() -> Self {
         let mut builder = reqwest::ClientBuilder::new();
@@ -27,6 +34,10 @@ impl BilibiliVideo {
 }
 
 impl Plugin for BilibiliVideo {
+    fn get_id(&self) -> &str {
+        "BilibiliVideo"
+    }
+
     fn get_name(&self) -> &str {
         "B站视频解析"
     }
@@ -38,14 +49,18 @@ impl Plugin for BilibiliVideo {
 
 #[async_trait]
 impl RawPlugin for BilibiliVideo {
-    async fn on_event(&self, event: &MessageEvent) -> anyhow::Result<bool> {
+    async fn on_event(
+        &self,
+        event: &MessageEvent,
+        context: &PluginContext,
+    ) -> anyhow::Result<bool> {
         let text = event.message_content();
         let tuple = self.parse_api(text).await;
         if tuple.is_none() {
             return Ok(false);
         }
         let tuple = tuple.unwrap();
-        let response = reqwest::get(tuple.0).await?;
+        let response = context.http.get(tuple.0).send().await?;
         let line = response.text().await.unwrap();
         if !line.starts_with("{\"code\":0") {
             return Ok(false);
@@ -95,7 +110,10 @@ impl RawPlugin for BilibiliVideo {
         let mut reply_message = MessageChainBuilder::new().build();
         reply_message = reply_message
             .append(format!("链接: https://www.bilibili.com/video/{}", tuple.1).parse_text());
-        let bytes = reqwest::get(pic)
+        let bytes = context
+            .http
+            .get(pic)
+            .send()
             .await?
             .error_for_status()?
             .bytes()
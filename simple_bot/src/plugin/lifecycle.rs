@@ -0,0 +1,56 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use proc_qq::re_exports::ricq;
+use tracing::{error, info, warn};
+
+use super::context;
+
+// load every plugin before any event reaches them, a plugin failing to load is
+// reported and stays registered; only an invalid config section stops the start
+pub async fn load(client: Arc<ricq::Client>) -> anyhow::Result<()> {
+    context::init(client)?;
+    for roles in super::PLUGINS.iter() {
+        let plugin = roles.plugin;
+        if let Err(e) = plugin.on_load(context::get(plugin.get_id())?).await {
+            error!("插件 {} 加载失败: {:?}", plugin.get_id(), e);
+        }
+    }
+    super::scheduler::start();
+    Ok(())
+}
+
+// give every plugin until `deadline` to shut down together, then stop the scheduler; the
+// client must still be running, plugins may send a last message
+pub async fn shutdown(deadline: Duration) {
+    let shutdowns = super::PLUGINS.iter().map(|roles| async move {
+        let plugin = roles.plugin;
+        let result = match context::get(plugin.get_id()) {
+            Ok(context) => plugin.on_shutdown(context).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("插件 {} 关闭时出错: {:?}", plugin.get_id(), e);
+        }
+    });
+    match tokio::time::timeout(deadline, futures::future::join_all(shutdowns)).await {
+        Ok(_) => info!("所有插件已关闭"),
+        Err(_) => warn!("插件未能在{}秒内关闭，强制退出", deadline.as_secs()),
+    }
+    super::scheduler::stop();
+}
+
+// resolves on SIGINT, or SIGTERM on unix
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("无法监听SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+// at most `count` calls within `period`, written as "count/period" like "1/30s" or "5/1m"
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rate {
+    pub count: usize,
+    pub period: Duration,
+}
+
+impl FromStr for Rate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (count, period) = simple_bot_syntax::parse_rate(s)?;
+        Ok(Rate { count, period })
+    }
+}
+
+impl TryFrom<String> for Rate {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for Rate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}s", self.count, self.period.as_secs())
+    }
+}
+
+impl From<Rate> for String {
+    fn from(rate: Rate) -> Self {
+        rate.to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct RateLimit {
+    #[serde(default)]
+    pub user: Option<Rate>,
+    #[serde(default)]
+    pub group: Option<Rate>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Bucket {
+    User(String, i64),
+    Group(String, i64),
+}
+
+// the calls still inside the period of each bucket, with the period they were counted for
+type Buckets = HashMap<Bucket, (Duration, VecDeque<Instant>)>;
+
+lazy_static! {
+    static ref BUCKETS: Mutex<Buckets> = Mutex::new(HashMap::new());
+}
+
+// record a call of `key`, or return how long to wait when one of the limits is reached
+pub fn acquire(
+    key: &str,
+    limit: &RateLimit,
+    user: i64,
+    group: Option<i64>,
+) -> Result<(), Duration> {
+    let mut buckets = BUCKETS.lock().unwrap();
+    acquire_at(&mut buckets, key, limit, user, group, Instant::now())
+}
+
+fn acquire_at(
+    buckets: &mut Buckets,
+    key: &str,
+    limit: &RateLimit,
+    user: i64,
+    group: Option<i64>,
+    now: Instant,
+) -> Result<(), Duration> {
+    // a bucket whose last call has left its period counts nothing anymore
+    buckets.retain(|_, (period, calls)| {
+        calls.back().is_some_and(|last| now.duration_since(*last) < *period)
+    });
+    let mut checks = Vec::new();
+    if let Some(rate) = limit.user {
+        checks.push((Bucket::User(key.to_string(), user), rate));
+    }
+    if let (Some(rate), Some(group)) = (limit.group, group) {
+        checks.push((Bucket::Group(key.to_string(), group), rate));
+    }
+    let mut wait = Duration::ZERO;
+    for (bucket, rate) in &checks {
+        let (period, calls) = buckets
+            .entry(bucket.clone())
+            .or_insert_with(|| (rate.period, VecDeque::new()));
+        *period = rate.period;
+        while calls
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= rate.period)
+        {
+            calls.pop_front();
+        }
+        if calls.len() >= rate.count {
+            let first = calls[calls.len() - rate.count];
+            wait = wait.max(rate.period - now.duration_since(first));
+        }
+    }
+    if !wait.is_zero() {
+        return Err(wait);
+    }
+    for (bucket, _) in &checks {
+        if let Some((_, calls)) = buckets.get_mut(bucket) {
+            calls.push_back(now);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(user: Option<&str>, group: Option<&str>) -> RateLimit {
+        RateLimit {
+            user: user.map(|rate| rate.parse().unwrap()),
+            group: group.map(|rate| rate.parse().unwrap()),
+        }
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn allows_up_to_the_count() {
+        let mut buckets = Buckets::new();
+        let limit = limit(Some("2/10s"), None);
+        let start = Instant::now();
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 1, None, start), Ok(()));
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 1, None, start + secs(1)), Ok(()));
+        // other users and keys have buckets of their own
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 2, None, start + secs(1)), Ok(()));
+        assert_eq!(acquire_at(&mut buckets, "b", &limit, 1, None, start + secs(1)), Ok(()));
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 1, None, start + secs(2)), Err(secs(8)));
+    }
+
+    #[test]
+    fn waits_for_the_oldest_call_that_counts() {
+        let mut buckets = Buckets::new();
+        let start = Instant::now();
+        let three = limit(Some("3/10s"), None);
+        for i in 0..3 {
+            assert_eq!(acquire_at(&mut buckets, "a", &three, 1, None, start + secs(i)), Ok(()));
+        }
+        // lowered by a reload, the second call is the one that has to leave the period
+        let two = limit(Some("2/10s"), None);
+        assert_eq!(acquire_at(&mut buckets, "a", &two, 1, None, start + secs(3)), Err(secs(8)));
+    }
+
+    #[test]
+    fn group_limit_is_shared() {
+        let mut buckets = Buckets::new();
+        let limit = limit(Some("5/10s"), Some("1/10s"));
+        let start = Instant::now();
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 1, Some(9), start), Ok(()));
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 2, Some(9), start + secs(4)), Err(secs(6)));
+        // a private chat has no group to count
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 2, None, start + secs(4)), Ok(()));
+    }
+
+    #[test]
+    fn calls_leave_the_window() {
+        let mut buckets = Buckets::new();
+        let limit = limit(Some("1/10s"), None);
+        let start = Instant::now();
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 1, None, start), Ok(()));
+        assert!(acquire_at(&mut buckets, "a", &limit, 1, None, start + secs(9)).is_err());
+        assert_eq!(acquire_at(&mut buckets, "a", &limit, 1, None, start + secs(10)), Ok(()));
+        // expired buckets are dropped by the next call of anyone
+        assert_eq!(acquire_at(&mut buckets, "b", &limit, 2, None, start + secs(30)), Ok(()));
+        assert_eq!(buckets.len(), 1);
+    }
+}
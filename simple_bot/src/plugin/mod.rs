@@ -1,29 +1,182 @@
-use std::ops::Deref;
-use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use lazy_static::lazy_static;
-use proc_qq::{MessageChainPointTrait, MessageEvent};
+use proc_qq::{
+    FriendMessageRecallEvent, FriendPokeEvent, GroupLeaveEvent, GroupMessageRecallEvent,
+    GroupMuteEvent, GroupRequestEvent, MessageChainParseTrait, MessageChainPointTrait,
+    MessageEvent, MessageSendToSourceTrait, NewFriendRequestEvent, NewMemberEvent,
+    SelfInvitedEvent,
+};
 
+pub mod config;
+pub mod context;
+pub mod error;
 mod internal;
+pub mod lifecycle;
+pub mod limit;
+pub mod pattern;
+pub mod permission;
+mod registry;
+pub mod reload;
+pub mod scheduler;
+pub mod switch;
+
+pub use config::ConfigSpec;
+pub use context::PluginContext;
+pub use error::UserError;
+pub use permission::Role;
+pub use registry::{instance, module_actions, ActionRegistration, PluginRegistration, Roles};
 
 lazy_static! {
-    pub static ref COMMAND_PLUGINS: Arc<Vec<Box<dyn CommandPlugin + Send + Sync>>> =
-        Arc::new(internal::register_command_plugins());
+    pub static ref PLUGINS: Vec<registry::Roles> = registry::load();
 }
 
 lazy_static! {
-    pub static ref RAW_PLUGINS: Arc<Vec<Box<dyn RawPlugin + Send + Sync>>> =
-        Arc::new(internal::register_raw_plugins());
+    pub static ref COMMAND_PLUGINS: Vec<&'static (dyn CommandPlugin + Send + Sync)> =
+        PLUGINS.iter().filter_map(|roles| roles.command).collect();
+}
+
+lazy_static! {
+    pub static ref RAW_PLUGINS: Vec<&'static (dyn RawPlugin + Send + Sync)> =
+        PLUGINS.iter().filter_map(|roles| roles.raw).collect();
+}
+
+lazy_static! {
+    pub static ref SCHEDULED_PLUGINS: Vec<&'static (dyn ScheduledPlugin + Send + Sync)> =
+        PLUGINS.iter().filter_map(|roles| roles.scheduled).collect();
+}
+
+lazy_static! {
+    pub static ref NOTICE_PLUGINS: Vec<&'static (dyn NoticePlugin + Send + Sync)> =
+        by_priority(PLUGINS.iter().filter_map(|roles| roles.notice).collect());
+}
+
+lazy_static! {
+    pub static ref REQUEST_PLUGINS: Vec<&'static (dyn RequestPlugin + Send + Sync)> =
+        by_priority(PLUGINS.iter().filter_map(|roles| roles.request).collect());
+}
+
+fn by_priority<P: Plugin + ?Sized>(mut plugins: Vec<&'static P>) -> Vec<&'static P> {
+    plugins.sort_by_key(|plugin| std::cmp::Reverse(plugin.get_priority()));
+    plugins
+}
+
+lazy_static! {
+    // every hook sorted by priority, command hooks go first when priorities are equal
+    pub static ref DISPATCH_ORDER: Vec<Hook> = {
+        let mut hooks = Vec::new();
+        for plugin in COMMAND_PLUGINS.iter() {
+            hooks.push(Hook::Command(*plugin));
+        }
+        for plugin in RAW_PLUGINS.iter() {
+            hooks.push(Hook::Raw(*plugin));
+        }
+        hooks.sort_by_key(|hook| std::cmp::Reverse(hook.get_priority()));
+        hooks
+    };
+}
+
+pub enum Hook {
+    Command(&'static (dyn CommandPlugin + Send + Sync)),
+    Raw(&'static (dyn RawPlugin + Send + Sync)),
+}
+
+impl Hook {
+    pub fn get_id(&self) -> &'static str {
+        match self {
+            Hook::Command(plugin) => plugin.get_id(),
+            Hook::Raw(plugin) => plugin.get_id(),
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Hook::Command(plugin) => plugin.get_name(),
+            Hook::Raw(plugin) => plugin.get_name(),
+        }
+    }
+
+    pub fn get_desc(&self) -> &'static str {
+        match self {
+            Hook::Command(plugin) => plugin.get_desc(),
+            Hook::Raw(plugin) => plugin.get_desc(),
+        }
+    }
+
+    pub fn get_priority(&self) -> i32 {
+        match self {
+            Hook::Command(plugin) => plugin.get_priority(),
+            Hook::Raw(plugin) => plugin.get_priority(),
+        }
+    }
+
+    pub fn is_consuming(&self) -> bool {
+        match self {
+            Hook::Command(plugin) => plugin.is_consuming(),
+            Hook::Raw(plugin) => plugin.is_consuming(),
+        }
+    }
+
+    // `Some(matched)` for a consuming command plugin, which takes the event as soon as one of
+    // its patterns matches; other hooks only tell whether they handled it by running
+    pub fn claims(&self, event: &MessageEvent) -> Option<bool> {
+        match self {
+            Hook::Command(plugin) if plugin.is_consuming() => Some(plugin.matches(event)),
+            _ => None,
+        }
+    }
+
+    pub async fn on_event(&self, event: &MessageEvent) -> anyhow::Result<bool> {
+        let context = context::get(self.get_id())?;
+        match self {
+            Hook::Command(plugin) => plugin.on_event(event, context).await,
+            Hook::Raw(plugin) => plugin.on_event(event, context).await,
+        }
+    }
 }
 
+#[async_trait]
 pub trait Plugin {
+    // stable identifier used by the per-group switches stored in the database and by the
+    // `plugins.<id>` section of config.yml, so it must not change with a rename of the type
+    fn get_id(&self) -> &str;
     fn get_name(&self) -> &str;
     fn get_desc(&self) -> &str;
+
+    // plugins with a higher priority see the event first
+    fn get_priority(&self) -> i32 {
+        0
+    }
+
+    // whether a handled event stops here instead of reaching the remaining plugins
+    fn is_consuming(&self) -> bool {
+        true
+    }
+
+    // the type of the `plugins.<id>` section of config.yml, `None` for plugins without settings
+    fn get_config_spec(&self) -> Option<ConfigSpec> {
+        None
+    }
+
+    // called once at startup before the plugin sees any event
+    async fn on_load(&self, _context: &PluginContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // called once on SIGINT or SIGTERM, `main` only waits until the shutdown deadline
+    async fn on_shutdown(&self, _context: &PluginContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    // called after a reload changed the `plugins.<id>` section, `context` already has the new one
+    async fn on_reload(&self, _context: &PluginContext) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 pub trait ActionSelector {
-    fn select(&self, event: &MessageEvent) -> Option<Vec<String>>;
+    fn select(&self, event: &MessageEvent) -> Option<anyhow::Result<pattern::Args>>;
 }
 
 #[async_trait]
@@ -31,67 +184,256 @@ pub trait Action: Send + Sync {
     async fn do_action(
         &self,
         event: &MessageEvent,
-        slot_content: Vec<String>,
+        context: &PluginContext,
+        args: pattern::Args,
     ) -> anyhow::Result<bool>;
 
-    fn get_pattern(&self) -> String;
+    fn get_pattern(&self) -> &pattern::Pattern;
+
+    // name of the function behind the action
+    fn get_name(&self) -> &str;
+
+    // the pattern with the type of every parameter
+    fn get_usage(&self) -> String {
+        self.get_pattern().usage().to_string()
+    }
+
+    fn get_desc(&self) -> &str {
+        ""
+    }
+
+    fn get_role(&self) -> Role {
+        Role::Member
+    }
+
+    fn get_rate_limit(&self) -> limit::RateLimit {
+        limit::RateLimit::default()
+    }
 }
 
 impl<T: Action + ?Sized> ActionSelector for T {
-    fn select(&self, event: &MessageEvent) -> Option<Vec<String>> {
-        let message_chain = event.message_chain();
-        let mut message_content = String::new();
-        let elements = message_chain.clone().into_iter().filter(|e| match e {
-            proc_qq::re_exports::ricq_core::msg::elem::RQElem::Other(_) => false,
-            _ => true,
-        });
-        for element in elements {
-            match element {
-                proc_qq::re_exports::ricq_core::msg::elem::RQElem::Text(text) => {
-                    message_content.push_str(&text.content);
-                }
-                proc_qq::re_exports::ricq_core::msg::elem::RQElem::Other(_) => {}
-                _ => {
-                    message_content.push_str("");
-                }
-            }
+    fn select(&self, event: &MessageEvent) -> Option<anyhow::Result<pattern::Args>> {
+        self.get_pattern().captures_message(event.message_chain())
+    }
+}
+
+// the text elements of a message joined together
+fn message_text(event: &MessageEvent) -> String {
+    let mut message_content = String::new();
+    for element in event.message_chain().clone().into_iter() {
+        if let proc_qq::re_exports::ricq_core::msg::elem::RQElem::Text(text) = element {
+            message_content.push_str(&text.content);
         }
-        let pattern = &self.get_pattern();
-        let pattern = ARG_REGEX.replace_all(pattern, r"(.*)");
-        let regex = regex::Regex::new(pattern.deref()).ok()?;
-        let captures = regex.captures(&message_content);
-        captures.map(|captures| {
-            captures
-                .iter()
-                .into_iter()
-                .skip(1)
-                .filter(|o| o.is_some())
-                .map(|cap| cap.unwrap().as_str().to_string())
-                .collect::<Vec<String>>()
-        })
     }
+    message_content
 }
 
-lazy_static! {
-    static ref ARG_REGEX: regex::Regex = regex::Regex::new(r"\{\w+\}").unwrap();
+// what follows the root of a command plugin, `None` when the text does not start with it
+fn strip_root<'a>(text: &'a str, root: &str) -> Option<&'a str> {
+    match text.trim().strip_prefix(root) {
+        Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => Some(rest.trim()),
+        _ => None,
+    }
+}
+
+// "/keyword help" output, every action under the root with its arguments and description
+fn usage_tree(root: &str, name: &str, actions: &[Box<dyn Action>]) -> String {
+    let mut tree = format!("{} {}", root, name);
+    let mut lines = actions
+        .iter()
+        .map(|action| {
+            let usage = action.get_usage();
+            let usage = usage.strip_prefix(root).unwrap_or(&usage).trim_start().to_string();
+            (usage, action.get_desc().to_string())
+        })
+        .collect::<Vec<_>>();
+    lines.push(("help".to_string(), "查看此帮助".to_string()));
+    for (i, (usage, desc)) in lines.iter().enumerate() {
+        let branch = if i + 1 == lines.len() { "└─" } else { "├─" };
+        tree.push_str(&format!("\n{} {}", branch, usage));
+        if !desc.is_empty() {
+            tree.push_str(&format!("  {}", desc));
+        }
+    }
+    tree
 }
 
 #[async_trait]
 pub trait CommandPlugin: Plugin + Send + Sync {
-    async fn on_event(&self, event: &MessageEvent) -> anyhow::Result<bool> {
+    async fn on_event(
+        &self,
+        event: &MessageEvent,
+        context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        if let Some(root) = self.get_root() {
+            let text = message_text(event);
+            let Some(sub_command) = strip_root(&text, root) else {
+                // none of the actions can match without the root in front
+                return Ok(false);
+            };
+            if sub_command.is_empty() || sub_command == "help" {
+                let tree = usage_tree(root, self.get_name(), self.get_actions());
+                event
+                    .send_message_to_source(tree.parse_message_chain())
+                    .await
+                    .unwrap();
+                return Ok(true);
+            }
+        }
+        // only the first matching action runs, a denial is reported like any other failure
         for action in self.get_actions() {
-            let select_res = action.select(event);
-            if let Some(slot_content) = select_res {
-                let _ = action.do_action(event, slot_content).await;
+            let Some(args) = action.select(event) else {
+                continue;
+            };
+            let result: anyhow::Result<bool> = async {
+                let args = args?;
+                if !permission::check_role(event, action.get_role()).await {
+                    return Err(UserError::new("权限不足").into());
+                }
+                let key = format!("{}::{}", self.get_id(), action.get_name());
+                let rate_limit = crate::CONFIG
+                    .load()
+                    .rate_limits
+                    .get(&key)
+                    .copied()
+                    .unwrap_or_else(|| action.get_rate_limit());
+                let group = event.as_group_message().ok().map(|e| e.inner.group_code);
+                if !permission::is_owner(event.from_uin()) {
+                    if let Err(wait) = limit::acquire(&key, &rate_limit, event.from_uin(), group) {
+                        let message = format!("操作太频繁，请在{}秒后再试", wait.as_secs() + 1);
+                        return Err(UserError::new(message).into());
+                    }
+                }
+                action.do_action(event, context, args).await
+            }
+            .await;
+            if let Err(e) = result {
+                error::report(event, self.get_id(), action.get_pattern().as_str(), e).await;
+            }
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // whether `on_event` would answer the event: the usage under the root, or an action
+    fn matches(&self, event: &MessageEvent) -> bool {
+        if let Some(root) = self.get_root() {
+            match strip_root(&message_text(event), root) {
+                None => return false,
+                Some(sub_command) if sub_command.is_empty() || sub_command == "help" => return true,
+                Some(_) => {}
             }
         }
-        Ok(true)
+        self.get_actions()
+            .iter()
+            .any(|action| action.select(event).is_some())
     }
 
     fn get_actions(&self) -> &Vec<Box<dyn Action>>;
+
+    // the command every action starts with, like "/keyword", which then answers
+    // "/keyword" and "/keyword help" with the usage of all of them
+    fn get_root(&self) -> Option<&str> {
+        None
+    }
 }
 
 #[async_trait]
 pub trait RawPlugin: Plugin {
-    async fn on_event(&self, event: &MessageEvent) -> anyhow::Result<bool>;
+    async fn on_event(
+        &self,
+        event: &MessageEvent,
+        context: &PluginContext,
+    ) -> anyhow::Result<bool>;
+}
+
+// group and friend notices, a plugin only overrides the ones it cares about,
+// returning whether it handled the notice like `RawPlugin::on_event`
+#[async_trait]
+pub trait NoticePlugin: Plugin {
+    async fn on_member_join(
+        &self,
+        _event: &NewMemberEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    async fn on_member_leave(
+        &self,
+        _event: &GroupLeaveEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    async fn on_group_recall(
+        &self,
+        _event: &GroupMessageRecallEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    async fn on_friend_recall(
+        &self,
+        _event: &FriendMessageRecallEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    async fn on_group_mute(
+        &self,
+        _event: &GroupMuteEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    async fn on_friend_poke(
+        &self,
+        _event: &FriendPokeEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+}
+
+// requests waiting for an answer, which is given through the client of the event
+#[async_trait]
+pub trait RequestPlugin: Plugin {
+    async fn on_friend_request(
+        &self,
+        _event: &NewFriendRequestEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    // the bot is invited into a group
+    async fn on_group_invitation(
+        &self,
+        _event: &SelfInvitedEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    // someone asks to join a group the bot manages
+    async fn on_join_request(
+        &self,
+        _event: &GroupRequestEvent,
+        _context: &PluginContext,
+    ) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+}
+
+// ticks every `get_interval` on the central scheduler, see `scheduler::start`
+#[async_trait]
+pub trait ScheduledPlugin: Plugin {
+    fn get_interval(&self) -> Duration;
+
+    async fn on_tick(&self, context: &PluginContext) -> anyhow::Result<()>;
 }
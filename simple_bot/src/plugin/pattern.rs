@@ -0,0 +1,529 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use proc_qq::re_exports::ricq_core::msg::elem::{Reply, RQElem};
+use proc_qq::re_exports::ricq_core::msg::MessageChain;
+use regex::Regex;
+use simple_bot_syntax::{parse_pattern, OptionSpec, Segment, Syntax};
+
+use crate::plugin::UserError;
+
+// a single word, or a quoted string which may contain spaces and escaped quotes
+const TOKEN: &str = r#""(?:[^"\\]|\\.)*"|[^\s\x{FFFC}]+"#;
+
+// a non-text element stands in the text as \u{FFFC}, its kind, its index and \u{FFFC}
+const MARKER: char = '\u{FFFC}';
+
+lazy_static! {
+    // same as TOKEN, but an option like --name="a b" is kept in one piece
+    static ref OPTION_TOKEN: Regex =
+        Regex::new(r#""(?:[^"\\]|\\.)*"|--[\w-]+="(?:[^"\\]|\\.)*"|\S+"#).unwrap();
+    static ref MARKER_REGEX: Regex = Regex::new(r"\x{FFFC}[a-z]\d+\x{FFFC}").unwrap();
+}
+
+// a command pattern such as "/keyword add {chance:int} {keyword} {reply..}"
+//
+// literal text is matched as it is, any whitespace in it matches any amount of whitespace,
+// and every placeholder takes one token of the message. a placeholder may be followed by
+// a constraint: `int`, `uint`, `float`, choices like `1|2` or a regex like `/\d{6}/`,
+// the last placeholder may also be written as `{name..}` to take the rest of the message
+//
+// flags like `[--all]` and options like `[--min-sim=<f64>]` may be given anywhere in the
+// message, they are taken out of it before the rest is matched
+//
+// a placeholder may also take a non-text element of the message instead of a word, which
+// the action declares with `with_elements`, and `with_optional` makes one of them optional
+pub struct Pattern {
+    raw: String,
+    usage: String,
+    regex: Regex,
+    // the same without the tokens of numbers, see `compile`
+    loose_regex: Option<Regex>,
+    segments: Vec<Segment>,
+    placeholders: Vec<Placeholder>,
+    options: Vec<OptionSpec>,
+}
+
+struct Placeholder {
+    // as parsed from the pattern
+    parsed: simple_bot_syntax::Placeholder,
+    // declared by the action, see `with_elements` and `with_optional`
+    optional: bool,
+    element: Option<ElementKind>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElementKind {
+    At,
+    // a group or a friend image
+    Image,
+    Face,
+    Any,
+}
+
+impl ElementKind {
+    // the marker kinds it accepts
+    fn kinds(&self) -> &'static str {
+        match self {
+            ElementKind::At => "a",
+            ElementKind::Image => "i",
+            ElementKind::Face => "f",
+            ElementKind::Any => "aife",
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            ElementKind::At => "@某人",
+            ElementKind::Image => "图片",
+            ElementKind::Face => "表情",
+            ElementKind::Any => "消息元素",
+        }
+    }
+}
+
+fn marker_kind(element: &RQElem) -> Option<char> {
+    match element {
+        RQElem::Text(_) | RQElem::Other(_) => None,
+        RQElem::At(_) => Some('a'),
+        RQElem::GroupImage(_) | RQElem::FriendImage(_) => Some('i'),
+        RQElem::Face(_) => Some('f'),
+        _ => Some('e'),
+    }
+}
+
+impl Pattern {
+    pub fn new(raw: &str) -> anyhow::Result<Self> {
+        let Syntax {
+            segments,
+            placeholders,
+            options,
+        } = parse_pattern(raw).map_err(|e| anyhow!("{}: {}", e, raw))?;
+        let mut usage = String::new();
+        for segment in &segments {
+            match segment {
+                Segment::Literal(literal) => usage.push_str(literal),
+                Segment::Placeholder(i) => usage.push_str(&format!("<{}>", placeholders[*i].spec)),
+            }
+        }
+        for option in &options {
+            match &option.value_type {
+                Some(value_type) => usage.push_str(&format!(" [--{}=<{}>]", option.name, value_type)),
+                None => usage.push_str(&format!(" [--{}]", option.name)),
+            }
+        }
+        let placeholders = placeholders
+            .into_iter()
+            .map(|parsed| Placeholder {
+                parsed,
+                optional: false,
+                element: None,
+            })
+            .collect::<Vec<_>>();
+        let (regex, loose_regex) = compile(&segments, &placeholders)?;
+        Ok(Pattern {
+            raw: raw.to_string(),
+            usage,
+            regex,
+            loose_regex,
+            segments,
+            placeholders,
+            options,
+        })
+    }
+
+    // let the named placeholders take an element of the given kind instead of a word
+    pub fn with_elements(mut self, elements: &[(&str, ElementKind)]) -> anyhow::Result<Self> {
+        for (name, kind) in elements {
+            if let Some(placeholder) = self.placeholders.iter_mut().find(|p| p.parsed.name == *name) {
+                placeholder.element = Some(*kind);
+            }
+        }
+        (self.regex, self.loose_regex) = compile(&self.segments, &self.placeholders)?;
+        Ok(self)
+    }
+
+    // let the message leave out the named placeholders
+    pub fn with_optional(mut self, names: &[&str]) -> anyhow::Result<Self> {
+        for placeholder in self.placeholders.iter_mut() {
+            if names.contains(&placeholder.parsed.name.as_str()) {
+                placeholder.optional = true;
+            }
+        }
+        (self.regex, self.loose_regex) = compile(&self.segments, &self.placeholders)?;
+        Ok(self)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    // "/keyword add {chance:int} {reply..}" => "/keyword add <chance:int> <reply..>"
+    pub fn usage(&self) -> &str {
+        &self.usage
+    }
+
+    // None when the text is not this command, an error when it is but an argument
+    // does not satisfy its constraint
+    pub fn captures(&self, text: &str) -> Option<anyhow::Result<Args>> {
+        self.captures_elements(text, Vec::new())
+    }
+
+    // same as `captures`, the elements wanted by the placeholders are kept as markers
+    pub fn captures_message(&self, message_chain: &MessageChain) -> Option<anyhow::Result<Args>> {
+        let kinds = self
+            .placeholders
+            .iter()
+            .filter_map(|p| p.element)
+            .map(|kind| kind.kinds())
+            .collect::<String>();
+        let mut text = String::new();
+        let mut elements = Vec::new();
+        for element in message_chain.clone().into_iter() {
+            match (&element, marker_kind(&element)) {
+                (RQElem::Text(t), _) => text.push_str(&t.content),
+                (_, Some(kind)) if kinds.contains(kind) => {
+                    text.push_str(&format!("{}{}{}{}", MARKER, kind, elements.len(), MARKER));
+                    elements.push(element);
+                }
+                _ => {}
+            }
+        }
+        let mut args = self.captures_elements(&text, elements)?;
+        if let Ok(args) = &mut args {
+            args.reply = message_chain.reply();
+        }
+        Some(args)
+    }
+
+    fn captures_elements(&self, text: &str, elements: Vec<RQElem>) -> Option<anyhow::Result<Args>> {
+        let mut args = Args::default();
+        let (text, option_error) = self.take_options(text, &mut args);
+        let captures = self
+            .regex
+            .captures(&text)
+            .or_else(|| self.loose_regex.as_ref()?.captures(&text))?;
+        if let Some(e) = option_error {
+            return Some(Err(e.into()));
+        }
+        for placeholder in &self.placeholders {
+            let value = match captures.name(&placeholder.parsed.name) {
+                Some(value) => value.as_str(),
+                None => continue,
+            };
+            if let Some(kind) = placeholder.element {
+                let element = value
+                    .trim_matches(MARKER)
+                    .get(1..)
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .and_then(|index| elements.get(index));
+                match element {
+                    Some(element) => {
+                        args.elements.insert(placeholder.parsed.name.clone(), element.clone());
+                    }
+                    None => {
+                        return Some(Err(UserError::invalid_argument(
+                            &placeholder.parsed.name,
+                            value,
+                            kind.describe(),
+                            &self.usage,
+                        )
+                        .into()))
+                    }
+                }
+                continue;
+            }
+            // elements only have a meaning in their own placeholders
+            let value = MARKER_REGEX.replace_all(value, "");
+            let value = if placeholder.parsed.greedy {
+                value.trim().to_string()
+            } else {
+                unquote(&value)
+            };
+            if let Some(constraint) = &placeholder.parsed.constraint {
+                if !constraint.check(&value) {
+                    return Some(Err(UserError::invalid_argument(
+                        &placeholder.parsed.name,
+                        &value,
+                        &constraint.describe(),
+                        &self.usage,
+                    )
+                    .into()));
+                }
+            }
+            args.values.insert(placeholder.parsed.name.clone(), value);
+        }
+        Some(Ok(args))
+    }
+
+    // move the declared flags and options of the text into `args`, returning what is left
+    fn take_options(&self, text: &str, args: &mut Args) -> (String, Option<UserError>) {
+        if self.options.is_empty() {
+            return (text.to_string(), None);
+        }
+        let mut rest = String::new();
+        let mut error = None;
+        let mut last = 0;
+        for token in OPTION_TOKEN.find_iter(text) {
+            let (name, value) = match token.as_str().strip_prefix("--") {
+                Some(option) => match option.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (option, None),
+                },
+                None => continue,
+            };
+            let option = match self.options.iter().find(|o| o.name == name) {
+                Some(option) => option,
+                None => continue,
+            };
+            match (&option.value_type, value) {
+                (None, None) => {
+                    args.flags.insert(option.ident());
+                }
+                (Some(_), Some(value)) if !value.is_empty() => {
+                    args.values.insert(option.ident(), unquote(value));
+                }
+                (Some(_), _) => {
+                    error.get_or_insert_with(|| {
+                        UserError::missing_argument(&format!("--{}", name), &self.usage)
+                    });
+                }
+                // a flag does not take a value, leave it to the placeholders
+                (None, Some(_)) => continue,
+            }
+            rest.push_str(&text[last..token.start()]);
+            last = token.end();
+        }
+        rest.push_str(&text[last..]);
+        (rest, error)
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+// a placeholder constrained to a number only takes a number, so that "我说5分钟后提醒我" is
+// not taken for "{time:uint}分钟后提醒我". a message starting with the command word of the
+// pattern is the command all the same, it also gets a loose regex taking any token there,
+// and a reply that the argument is not a number
+fn compile(
+    segments: &[Segment],
+    placeholders: &[Placeholder],
+) -> anyhow::Result<(Regex, Option<Regex>)> {
+    let command = matches!(
+        segments.first(),
+        Some(Segment::Literal(literal)) if !literal.trim().is_empty()
+    );
+    let numbers = placeholders
+        .iter()
+        .any(|p| p.element.is_none() && number_token(p).is_some());
+    let loose = match command && numbers {
+        true => Some(compile_regex(segments, placeholders, false)?),
+        false => None,
+    };
+    Ok((compile_regex(segments, placeholders, true)?, loose))
+}
+
+fn number_token(placeholder: &Placeholder) -> Option<&'static str> {
+    placeholder.parsed.constraint.as_ref()?.token()
+}
+
+fn compile_regex(
+    segments: &[Segment],
+    placeholders: &[Placeholder],
+    strict: bool,
+) -> anyhow::Result<Regex> {
+    let mut regex = String::from(r"^\s*");
+    let mut after_element = false;
+    for segment in segments {
+        let placeholder = match segment {
+            Segment::Literal(literal) => {
+                push_literal(&mut regex, literal, after_element);
+                after_element = false;
+                continue;
+            }
+            Segment::Placeholder(i) => &placeholders[*i],
+        };
+        let separated = regex.ends_with(r"\s+");
+        if separated {
+            regex.truncate(regex.len() - r"\s+".len());
+        }
+        // elements are not typed, the space around them may be left out
+        let separator = match (separated, placeholder.element) {
+            (false, _) => "",
+            (true, Some(_)) => r"\s*",
+            (true, None) => r"\s+",
+        };
+        let token = match placeholder.element {
+            Some(kind) => format!(r"\x{{FFFC}}[{}]\d+\x{{FFFC}}", kind.kinds()),
+            None if placeholder.parsed.greedy => r"[\s\S]*".to_string(),
+            None => match number_token(placeholder) {
+                Some(token) if strict => token.to_string(),
+                _ => TOKEN.to_string(),
+            },
+        };
+        let capture = format!("(?P<{}>{})", placeholder.parsed.name, token);
+        if placeholder.parsed.greedy && placeholder.element.is_none() {
+            // the rest may be empty, so that a missing argument gets a usage reply
+            match separator {
+                "" => regex.push_str(&capture),
+                _ => regex.push_str(&format!("(?:{}|$){}", separator, capture)),
+            }
+        } else if placeholder.optional {
+            regex.push_str(&format!("(?:{}{})?", separator, capture));
+        } else {
+            regex.push_str(&format!("{}{}", separator, capture));
+        }
+        after_element = placeholder.element.is_some();
+    }
+    regex.push_str(r"\s*$");
+    Ok(Regex::new(&regex)?)
+}
+
+// `loose_start` lets the whitespace it starts with be left out
+fn push_literal(regex: &mut String, literal: &str, loose_start: bool) {
+    let mut in_space = false;
+    for (i, c) in literal.chars().enumerate() {
+        if c.is_whitespace() {
+            if !in_space {
+                regex.push_str(if loose_start && i == 0 { r"\s*" } else { r"\s+" });
+            }
+            in_space = true;
+        } else {
+            regex.push_str(&regex::escape(&c.to_string()));
+            in_space = false;
+        }
+    }
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_string();
+    }
+    let mut unquoted = String::new();
+    let mut chars = value[1..value.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            _ => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+// the values captured by the placeholders and options of a pattern, and the flags given
+#[derive(Default, Debug)]
+pub struct Args {
+    values: HashMap<String, String>,
+    flags: HashSet<String>,
+    elements: HashMap<String, RQElem>,
+    // the message quoted by this one
+    reply: Option<Reply>,
+}
+
+impl Args {
+    pub fn take_element(&mut self, name: &str) -> Option<RQElem> {
+        self.elements.remove(name)
+    }
+
+    pub fn take_reply(&mut self) -> Option<Reply> {
+        self.reply.take()
+    }
+
+    pub fn take(&mut self, name: &str) -> Option<String> {
+        self.values.remove(name)
+    }
+
+    pub fn flag(&mut self, name: &str) -> bool {
+        self.flags.remove(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captures(pattern: &Pattern, text: &str) -> Option<Args> {
+        pattern.captures(text).map(|args| args.unwrap())
+    }
+
+    #[test]
+    fn quoted_args() {
+        let pattern = Pattern::new("/say {first} {second}").unwrap();
+        let mut args = captures(&pattern, r#"/say "hello world" "a \"b\"""#).unwrap();
+        assert_eq!(args.take("first").as_deref(), Some("hello world"));
+        assert_eq!(args.take("second").as_deref(), Some(r#"a "b""#));
+        assert!(pattern.captures("/say hello").is_none());
+    }
+
+    #[test]
+    fn greedy_placeholder() {
+        let pattern = Pattern::new("/echo {text..}").unwrap();
+        let mut args = captures(&pattern, "/echo  a  b ").unwrap();
+        assert_eq!(args.take("text").as_deref(), Some("a  b"));
+        // left empty, so that the action replies with the usage
+        let mut args = captures(&pattern, "/echo").unwrap();
+        assert_eq!(args.take("text").as_deref(), Some(""));
+        assert!(pattern.captures("/echoes").is_none());
+    }
+
+    #[test]
+    fn optional_placeholder() {
+        let pattern = Pattern::new("/list {page}")
+            .unwrap()
+            .with_optional(&["page"])
+            .unwrap();
+        assert_eq!(captures(&pattern, "/list").unwrap().take("page"), None);
+        assert_eq!(captures(&pattern, "/list 2").unwrap().take("page").as_deref(), Some("2"));
+        assert!(pattern.captures("/list 2 3").is_none());
+    }
+
+    #[test]
+    fn options() {
+        let pattern = Pattern::new("/search {query} [--all] [--min-sim=<f64>]").unwrap();
+        let mut args = captures(&pattern, "/search --min-sim=60 cat --all").unwrap();
+        assert_eq!(args.take("query").as_deref(), Some("cat"));
+        assert_eq!(args.take("min_sim").as_deref(), Some("60"));
+        assert!(args.flag("all"));
+        assert!(matches!(pattern.captures("/search cat --min-sim"), Some(Err(_))));
+        assert!(matches!(pattern.captures("/search cat --min-sim="), Some(Err(_))));
+    }
+
+    #[test]
+    fn failing_constraint() {
+        let pattern = Pattern::new("/mode {mode:on|off} {code:/\\d{6}/}").unwrap();
+        let mut args = captures(&pattern, "/mode on 123456").unwrap();
+        assert_eq!(args.take("mode").as_deref(), Some("on"));
+        assert!(matches!(pattern.captures("/mode maybe 123456"), Some(Err(_))));
+        assert!(matches!(pattern.captures("/mode off 12345"), Some(Err(_))));
+    }
+
+    #[test]
+    fn number_constraint() {
+        let pattern = Pattern::new("{time:uint}分钟后提醒我{content..}").unwrap();
+        let mut args = captures(&pattern, "5分钟后提醒我喝水").unwrap();
+        assert_eq!(args.take("time").as_deref(), Some("5"));
+        assert_eq!(args.take("content").as_deref(), Some("喝水"));
+        assert!(pattern.captures("我说5分钟后提醒我喝水").is_none());
+        assert!(pattern.captures("几分钟后提醒我喝水").is_none());
+        // the command word says it is the command, the argument just is not a number
+        let pattern = Pattern::new("/roll {sides:int} {times:uint}").unwrap();
+        assert!(captures(&pattern, "/roll -6 2").is_some());
+        assert!(matches!(pattern.captures("/roll six 2"), Some(Err(_))));
+        assert!(matches!(pattern.captures("/roll 6 -2"), Some(Err(_))));
+        assert!(pattern.captures("/rolls 6 2").is_none());
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(Pattern::new("/say {text..} {more}").is_err());
+        assert!(Pattern::new("/say {text").is_err());
+        assert!(Pattern::new("/mode {mode:on|}").is_err());
+        assert!(Pattern::new("/search [--min-sim=<>]").is_err());
+    }
+}
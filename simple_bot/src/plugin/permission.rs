@@ -0,0 +1,125 @@
+use std::collections::HashSet;
+
+use lazy_static::lazy_static;
+use proc_qq::re_exports::ricq::structs::GroupMemberPermission;
+use proc_qq::MessageEvent;
+use tokio::sync::{OnceCell, RwLock};
+use tracing::error;
+
+use super::context;
+
+// ordered from the least to the most privileged
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Blacklisted,
+    Member,
+    // owner or administrator of the group the message comes from
+    GroupAdmin,
+    // configured in `owners` of config.yml
+    Owner,
+}
+
+lazy_static! {
+    // (group_id, user_id) of every blacklisted member
+    static ref BLACKLIST: OnceCell<RwLock<HashSet<(i64, i64)>>> = OnceCell::new();
+}
+
+async fn read_blacklist() -> anyhow::Result<HashSet<(i64, i64)>> {
+    let blacklist = context::repos().blacklist.all().await?;
+    Ok(blacklist.into_iter().map(|b| (b.group_id, b.user_id)).collect())
+}
+
+async fn load_blacklist() -> &'static RwLock<HashSet<(i64, i64)>> {
+    BLACKLIST
+        .get_or_init(|| async {
+            let blacklist = read_blacklist().await.unwrap_or_else(|e| {
+                error!("从数据库读取黑名单失败: {:?}", e);
+                HashSet::new()
+            });
+            RwLock::new(blacklist)
+        })
+        .await
+}
+
+// read the blacklist again, for changes made to the table behind the bot's back
+pub async fn reload() -> anyhow::Result<()> {
+    let blacklist = read_blacklist().await?;
+    *load_blacklist().await.write().await = blacklist;
+    Ok(())
+}
+
+pub fn is_owner(user_id: i64) -> bool {
+    crate::CONFIG.load().owners.contains(&user_id)
+}
+
+pub async fn is_blacklisted(group_id: i64, user_id: i64) -> bool {
+    load_blacklist()
+        .await
+        .read()
+        .await
+        .contains(&(group_id, user_id))
+}
+
+pub async fn set_blacklisted(group_id: i64, user_id: i64, blacklisted: bool) -> anyhow::Result<()> {
+    let blacklist = load_blacklist().await;
+    if blacklisted {
+        context::repos().blacklist.add(group_id, user_id).await?;
+        blacklist.write().await.insert((group_id, user_id));
+    } else {
+        context::repos().blacklist.remove(group_id, user_id).await?;
+        blacklist.write().await.remove(&(group_id, user_id));
+    }
+    Ok(())
+}
+
+pub async fn get_blacklist(group_id: i64) -> Vec<i64> {
+    let mut users = load_blacklist()
+        .await
+        .read()
+        .await
+        .iter()
+        .filter(|(group, _)| *group == group_id)
+        .map(|(_, user)| *user)
+        .collect::<Vec<i64>>();
+    users.sort();
+    users
+}
+
+pub async fn resolve_role(event: &MessageEvent) -> Role {
+    if is_owner(event.from_uin()) {
+        return Role::Owner;
+    }
+    let event = match event.as_group_message() {
+        Ok(event) => event,
+        Err(_) => return Role::Member,
+    };
+    let group_id = event.inner.group_code;
+    let user_id = event.inner.from_uin;
+    if is_blacklisted(group_id, user_id).await {
+        return Role::Blacklisted;
+    }
+    match event.client.get_group_member_info(group_id, user_id).await {
+        Ok(info) => match info.permission {
+            GroupMemberPermission::Owner | GroupMemberPermission::Administrator => Role::GroupAdmin,
+            _ => Role::Member,
+        },
+        Err(e) => {
+            error!("获取群成员信息失败: {:?}", e);
+            Role::Member
+        }
+    }
+}
+
+pub async fn check_role(event: &MessageEvent, required: Role) -> bool {
+    if is_owner(event.from_uin()) {
+        return true;
+    }
+    // querying the group role needs a request, skip it when membership is enough
+    if required <= Role::Member {
+        if let Ok(event) = event.as_group_message() {
+            return !is_blacklisted(event.inner.group_code, event.inner.from_uin).await;
+        }
+        return true;
+    }
+    resolve_role(event).await >= required
+}
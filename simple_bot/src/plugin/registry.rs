@@ -0,0 +1,117 @@
+use tracing::error;
+
+use super::{
+    Action, CommandPlugin, NoticePlugin, Plugin, RawPlugin, RequestPlugin, ScheduledPlugin,
+};
+
+// submitted once by every plugin, see `Roles`
+pub struct PluginRegistration(pub fn() -> Roles);
+
+// submitted by every `#[action]`
+pub struct ActionRegistration {
+    pub module: &'static str,
+    pub line: u32,
+    pub create: fn() -> anyhow::Result<Box<dyn Action>>,
+}
+
+inventory::collect!(PluginRegistration);
+inventory::collect!(ActionRegistration);
+
+// one plugin instance and every hook it implements, like
+// `Roles::new(keyword).command(keyword).raw(keyword)`
+pub struct Roles {
+    pub plugin: &'static (dyn Plugin + Send + Sync),
+    pub command: Option<&'static (dyn CommandPlugin + Send + Sync)>,
+    pub raw: Option<&'static (dyn RawPlugin + Send + Sync)>,
+    pub scheduled: Option<&'static (dyn ScheduledPlugin + Send + Sync)>,
+    pub notice: Option<&'static (dyn NoticePlugin + Send + Sync)>,
+    pub request: Option<&'static (dyn RequestPlugin + Send + Sync)>,
+}
+
+impl Roles {
+    pub fn new(plugin: &'static (dyn Plugin + Send + Sync)) -> Self {
+        Roles {
+            plugin,
+            command: None,
+            raw: None,
+            scheduled: None,
+            notice: None,
+            request: None,
+        }
+    }
+
+    pub fn command(mut self, plugin: &'static (dyn CommandPlugin + Send + Sync)) -> Self {
+        self.command = Some(plugin);
+        self
+    }
+
+    pub fn raw(mut self, plugin: &'static (dyn RawPlugin + Send + Sync)) -> Self {
+        self.raw = Some(plugin);
+        self
+    }
+
+    pub fn scheduled(mut self, plugin: &'static (dyn ScheduledPlugin + Send + Sync)) -> Self {
+        self.scheduled = Some(plugin);
+        self
+    }
+
+    pub fn notice(mut self, plugin: &'static (dyn NoticePlugin + Send + Sync)) -> Self {
+        self.notice = Some(plugin);
+        self
+    }
+
+    pub fn request(mut self, plugin: &'static (dyn RequestPlugin + Send + Sync)) -> Self {
+        self.request = Some(plugin);
+        self
+    }
+}
+
+// plugins live as long as the bot, so all of their roles can share one instance
+pub fn instance<T: Send + Sync + 'static>(plugin: T) -> &'static T {
+    Box::leak(Box::new(plugin))
+}
+
+// every registered plugin, sorted by id since the link order is not stable; two plugins
+// with the same id would share their switches and config section, so the bot refuses to start
+pub fn load() -> Vec<Roles> {
+    let mut plugins = inventory::iter::<PluginRegistration>
+        .into_iter()
+        .map(|registration| (registration.0)())
+        .collect::<Vec<_>>();
+    plugins.sort_by(|a, b| a.plugin.get_id().cmp(b.plugin.get_id()));
+    for pair in plugins.windows(2) {
+        let (first, second) = (pair[0].plugin, pair[1].plugin);
+        if first.get_id() == second.get_id() {
+            panic!(
+                "插件 {} 与 {} 的 id 都是 {}，请用 #[plugin(id = \"...\")] 为其中一个指定其他 id",
+                first.get_name(),
+                second.get_name(),
+                first.get_id()
+            );
+        }
+    }
+    plugins
+}
+
+// the actions defined in a module, in the order they are written; an action whose pattern
+// does not compile is left out, so the rest of the plugin still works
+pub fn module_actions(module: &str) -> Vec<Box<dyn Action>> {
+    let mut registrations = inventory::iter::<ActionRegistration>
+        .into_iter()
+        .filter(|registration| registration.module == module)
+        .collect::<Vec<_>>();
+    registrations.sort_by_key(|registration| registration.line);
+    registrations
+        .into_iter()
+        .filter_map(|registration| match (registration.create)() {
+            Ok(action) => Some(action),
+            Err(e) => {
+                error!(
+                    "{} 第 {} 行的动作无法创建，已忽略: {:?}",
+                    registration.module, registration.line, e
+                );
+                None
+            }
+        })
+        .collect()
+}
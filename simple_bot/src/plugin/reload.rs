@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{error, info, warn};
+
+use super::context;
+
+lazy_static! {
+    // the watcher and `/reload` may fire together
+    static ref RELOADING: Mutex<()> = Mutex::new(());
+}
+
+// read config.yml again and swap it in, an invalid file leaves the running config untouched
+pub async fn reload() -> anyhow::Result<()> {
+    let _reloading = RELOADING.lock().await;
+    let config = crate::config::load_config()?;
+    // parse every section before swapping anything, so a failure changes nothing
+    let mut sections = HashMap::new();
+    for roles in super::PLUGINS.iter() {
+        let plugin = roles.plugin;
+        let id = plugin.get_id();
+        let Some(spec) = plugin.get_config_spec() else {
+            continue;
+        };
+        let section = config.plugins.get(id).cloned();
+        let section = spec.parse(id, section.unwrap_or_else(|| spec.default_section()))?;
+        sections.insert(id, section);
+    }
+    let old = crate::CONFIG.swap(Arc::new(config));
+    let new = crate::CONFIG.load_full();
+    if old.database != new.database {
+        warn!("数据库配置的修改需要重启后生效");
+    }
+    for roles in super::PLUGINS.iter() {
+        let plugin = roles.plugin;
+        let id = plugin.get_id();
+        let context = match context::get(id) {
+            Ok(context) => context,
+            Err(e) => {
+                warn!("{:?}", e);
+                continue;
+            }
+        };
+        // a plugin without a section hears of every reload, to refresh what it has cached
+        if let Some(section) = sections.remove(id) {
+            context.set_config(Some(section));
+            if old.plugins.get(id) == new.plugins.get(id) {
+                continue;
+            }
+        }
+        if let Err(e) = plugin.on_reload(context).await {
+            warn!("插件 {} 重新加载配置时出错: {:?}", id, e);
+        }
+    }
+    // the switches and the blacklist are cached, `/reload` also picks up edits to their tables
+    if let Err(e) = super::switch::reload().await {
+        warn!("重新读取插件开关失败: {:?}", e);
+    }
+    if let Err(e) = super::permission::reload().await {
+        warn!("重新读取黑名单失败: {:?}", e);
+    }
+    info!("已重新加载 {}", crate::config::path().display());
+    Ok(())
+}
+
+// reload whenever config.yml changes on disk
+pub fn watch() {
+    let path = crate::config::path();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        // the directory is watched, since editors often replace the file instead of writing it
+        let changed = event
+            .paths
+            .iter()
+            .any(|changed| changed.file_name() == path.file_name());
+        if changed && (event.kind.is_modify() || event.kind.is_create()) {
+            let _ = tx.send(());
+        }
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("无法监听 {}，只能通过 /reload 重新加载: {:?}", path.display(), e);
+            return;
+        }
+    };
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    if let Err(e) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+        error!("无法监听 {}，只能通过 /reload 重新加载: {:?}", path.display(), e);
+        return;
+    }
+    tokio::spawn(async move {
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            // a save usually comes as several events, wait for the last one
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            while rx.try_recv().is_ok() {}
+            if let Err(e) = reload().await {
+                error!("配置文件无效，继续使用原配置: {:#}", e);
+            }
+        }
+    });
+}
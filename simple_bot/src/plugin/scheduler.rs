@@ -0,0 +1,112 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::handler::supervise;
+
+lazy_static! {
+    static ref SCHEDULER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+    // one-off jobs of `Scheduler::after` still waiting or running
+    static ref JOBS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+}
+
+// the handle a plugin schedules its own jobs with, see `crate::plugin::PluginContext`
+#[derive(Clone)]
+pub struct Scheduler {
+    plugin: &'static str,
+}
+
+impl Scheduler {
+    pub fn new(plugin: &'static str) -> Self {
+        Scheduler { plugin }
+    }
+
+    // run `job` once after `delay`, jobs that have not finished are dropped on shutdown
+    pub fn after<F>(&self, delay: Duration, job: F)
+    where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let plugin = self.plugin;
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            supervise(plugin, async { job.await.map(|_| true) }).await;
+        });
+        let mut jobs = JOBS.lock().unwrap();
+        jobs.retain(|job| !job.is_finished());
+        jobs.push(handle);
+    }
+}
+
+// one task keeps the next tick of every scheduled plugin, a tick still running when
+// the next one is due is skipped instead of piling up
+pub fn start() {
+    let plugins = super::SCHEDULED_PLUGINS
+        .iter()
+        .copied()
+        .filter(|plugin| {
+            if plugin.get_interval().is_zero() {
+                warn!("插件 {} 的执行间隔为0，已跳过", plugin.get_id());
+                return false;
+            }
+            true
+        })
+        .collect::<Vec<_>>();
+    if plugins.is_empty() {
+        return;
+    }
+    let handle = tokio::spawn(async move {
+        let now = Instant::now();
+        let mut queue = plugins
+            .iter()
+            .enumerate()
+            .map(|(i, plugin)| Reverse((now + plugin.get_interval(), i)))
+            .collect::<BinaryHeap<_>>();
+        let busy = plugins
+            .iter()
+            .map(|_| Arc::new(AtomicBool::new(false)))
+            .collect::<Vec<_>>();
+        // dropped with the scheduler, which aborts the ticks still running
+        let mut ticks = JoinSet::new();
+        while let Some(Reverse((due, i))) = queue.pop() {
+            tokio::time::sleep_until(due).await;
+            while ticks.try_join_next().is_some() {}
+            let plugin = plugins[i];
+            queue.push(Reverse((due + plugin.get_interval(), i)));
+            if busy[i].swap(true, Ordering::SeqCst) {
+                warn!(
+                    "插件 {} 的上一次执行尚未结束，跳过本次执行",
+                    plugin.get_id()
+                );
+                continue;
+            }
+            let busy = busy[i].clone();
+            ticks.spawn(async move {
+                supervise(plugin.get_id(), async {
+                    let context = super::context::get(plugin.get_id())?;
+                    plugin.on_tick(context).await.map(|_| true)
+                })
+                .await;
+                busy.store(false, Ordering::SeqCst);
+            });
+        }
+    });
+    *SCHEDULER.lock().unwrap() = Some(handle);
+}
+
+// no tick or job runs after this, including the ones in progress
+pub fn stop() {
+    if let Some(handle) = SCHEDULER.lock().unwrap().take() {
+        handle.abort();
+    }
+    for job in JOBS.lock().unwrap().drain(..) {
+        job.abort();
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+
+use lazy_static::lazy_static;
+use proc_qq::MessageEvent;
+use tokio::sync::{OnceCell, RwLock};
+use tracing::error;
+
+use super::context;
+
+lazy_static! {
+    // (group_id, plugin id) of every plugin switched off in a group
+    static ref DISABLED: OnceCell<RwLock<HashSet<(i64, String)>>> = OnceCell::new();
+}
+
+async fn read_disabled() -> anyhow::Result<HashSet<(i64, String)>> {
+    let disabled = context::repos().disabled_plugins.all().await?;
+    Ok(disabled.into_iter().map(|d| (d.group_id, d.plugin)).collect())
+}
+
+async fn load_disabled() -> &'static RwLock<HashSet<(i64, String)>> {
+    DISABLED
+        .get_or_init(|| async {
+            let disabled = read_disabled().await.unwrap_or_else(|e| {
+                error!("从数据库读取插件开关失败: {:?}", e);
+                HashSet::new()
+            });
+            RwLock::new(disabled)
+        })
+        .await
+}
+
+// read the switches again, for changes made to the table behind the bot's back
+pub async fn reload() -> anyhow::Result<()> {
+    let disabled = read_disabled().await?;
+    *load_disabled().await.write().await = disabled;
+    Ok(())
+}
+
+pub async fn is_enabled(event: &MessageEvent, plugin: &str) -> bool {
+    match event.as_group_message() {
+        Ok(event) => is_enabled_in_group(event.inner.group_code, plugin).await,
+        // plugins can only be switched off per group, private chats always see all of them
+        Err(_) => true,
+    }
+}
+
+pub async fn is_enabled_in_group(group_id: i64, plugin: &str) -> bool {
+    !load_disabled()
+        .await
+        .read()
+        .await
+        .contains(&(group_id, plugin.to_string()))
+}
+
+pub async fn set_enabled(group_id: i64, plugin: &str, enabled: bool) -> anyhow::Result<()> {
+    let disabled = load_disabled().await;
+    if enabled {
+        context::repos().disabled_plugins.enable(group_id, plugin).await?;
+        disabled.write().await.remove(&(group_id, plugin.to_string()));
+    } else {
+        context::repos().disabled_plugins.disable(group_id, plugin).await?;
+        disabled.write().await.insert((group_id, plugin.to_string()));
+    }
+    Ok(())
+}
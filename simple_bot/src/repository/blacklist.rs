@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use rbatis::RBatis;
+
+use crate::database::DIALECT;
+use crate::model::blacklist::Blacklist;
+
+// the users ignored in each group
+#[async_trait]
+pub trait BlacklistRepo: Send + Sync {
+    async fn all(&self) -> anyhow::Result<Vec<Blacklist>>;
+
+    // adding a user already in the blacklist does nothing
+    async fn add(&self, group_id: i64, user_id: i64) -> anyhow::Result<()>;
+
+    async fn remove(&self, group_id: i64, user_id: i64) -> anyhow::Result<()>;
+}
+
+pub struct SqlBlacklistRepo {
+    db: RBatis,
+}
+
+impl SqlBlacklistRepo {
+    pub fn new(db: RBatis) -> Self {
+        SqlBlacklistRepo { db }
+    }
+}
+
+#[async_trait]
+impl BlacklistRepo for SqlBlacklistRepo {
+    async fn all(&self) -> anyhow::Result<Vec<Blacklist>> {
+        Ok(Blacklist::select_all(&self.db).await?)
+    }
+
+    async fn add(&self, group_id: i64, user_id: i64) -> anyhow::Result<()> {
+        self.db
+            .exec(
+                &DIALECT.insert_ignore("blacklist", &["group_id", "user_id"]),
+                vec![rbs::to_value!(group_id), rbs::to_value!(user_id)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn remove(&self, group_id: i64, user_id: i64) -> anyhow::Result<()> {
+        self.db
+            .exec(
+                "DELETE FROM blacklist WHERE group_id = ? AND user_id = ?",
+                vec![rbs::to_value!(group_id), rbs::to_value!(user_id)],
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use rbatis::RBatis;
+
+use crate::database::DIALECT;
+use crate::model::plugin::DisabledPlugin;
+
+// the plugins switched off in each group, by plugin id
+#[async_trait]
+pub trait DisabledPluginRepo: Send + Sync {
+    async fn all(&self) -> anyhow::Result<Vec<DisabledPlugin>>;
+
+    // disabling a plugin twice does nothing
+    async fn disable(&self, group_id: i64, plugin: &str) -> anyhow::Result<()>;
+
+    async fn enable(&self, group_id: i64, plugin: &str) -> anyhow::Result<()>;
+}
+
+pub struct SqlDisabledPluginRepo {
+    db: RBatis,
+}
+
+impl SqlDisabledPluginRepo {
+    pub fn new(db: RBatis) -> Self {
+        SqlDisabledPluginRepo { db }
+    }
+}
+
+#[async_trait]
+impl DisabledPluginRepo for SqlDisabledPluginRepo {
+    async fn all(&self) -> anyhow::Result<Vec<DisabledPlugin>> {
+        Ok(DisabledPlugin::select_all(&self.db).await?)
+    }
+
+    async fn disable(&self, group_id: i64, plugin: &str) -> anyhow::Result<()> {
+        self.db
+            .exec(
+                &DIALECT.insert_ignore("disabled_plugin", &["group_id", "plugin"]),
+                vec![rbs::to_value!(group_id), rbs::to_value!(plugin)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn enable(&self, group_id: i64, plugin: &str) -> anyhow::Result<()> {
+        self.db
+            .exec(
+                "DELETE FROM disabled_plugin WHERE group_id = ? AND plugin = ?",
+                vec![rbs::to_value!(group_id), rbs::to_value!(plugin)],
+            )
+            .await?;
+        Ok(())
+    }
+}
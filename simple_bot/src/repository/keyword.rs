@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use rbatis::RBatis;
+
+use crate::model::keyword::KeyWord;
+
+#[async_trait]
+pub trait KeyWordRepo: Send + Sync {
+    async fn all(&self) -> anyhow::Result<Vec<KeyWord>>;
+
+    async fn get(&self, id: i64) -> anyhow::Result<Option<KeyWord>>;
+
+    async fn add(&self, keyword: KeyWord) -> anyhow::Result<()>;
+
+    // overwrite the row with the same id
+    async fn update(&self, keyword: KeyWord) -> anyhow::Result<()>;
+
+    async fn remove(&self, id: i64) -> anyhow::Result<()>;
+}
+
+pub struct SqlKeyWordRepo {
+    db: RBatis,
+}
+
+impl SqlKeyWordRepo {
+    pub fn new(db: RBatis) -> Self {
+        SqlKeyWordRepo { db }
+    }
+}
+
+#[async_trait]
+impl KeyWordRepo for SqlKeyWordRepo {
+    async fn all(&self) -> anyhow::Result<Vec<KeyWord>> {
+        Ok(KeyWord::select_all(&self.db).await?)
+    }
+
+    async fn get(&self, id: i64) -> anyhow::Result<Option<KeyWord>> {
+        let keywords = KeyWord::select_by_column(&self.db, "id", id).await?;
+        Ok(keywords.into_iter().next())
+    }
+
+    async fn add(&self, keyword: KeyWord) -> anyhow::Result<()> {
+        KeyWord::insert(&self.db, &keyword).await?;
+        Ok(())
+    }
+
+    async fn update(&self, keyword: KeyWord) -> anyhow::Result<()> {
+        KeyWord::update_by_column(&self.db, &keyword, "id").await?;
+        Ok(())
+    }
+
+    async fn remove(&self, id: i64) -> anyhow::Result<()> {
+        KeyWord::delete_by_column(&self.db, "id", id).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use rbatis::RBatis;
+
+use crate::database::DIALECT;
+use crate::model::kv::PluginKv;
+
+// the values behind `crate::plugin::context::KvStore`, already serialized
+#[async_trait]
+pub trait KvRepo: Send + Sync {
+    async fn get(&self, plugin: &str, name: &str) -> anyhow::Result<Option<String>>;
+
+    // insert, or overwrite the value of the same name
+    async fn set(&self, plugin: &str, name: &str, value: &str) -> anyhow::Result<()>;
+
+    async fn remove(&self, plugin: &str, name: &str) -> anyhow::Result<()>;
+}
+
+pub struct SqlKvRepo {
+    db: RBatis,
+}
+
+impl SqlKvRepo {
+    pub fn new(db: RBatis) -> Self {
+        SqlKvRepo { db }
+    }
+}
+
+#[async_trait]
+impl KvRepo for SqlKvRepo {
+    async fn get(&self, plugin: &str, name: &str) -> anyhow::Result<Option<String>> {
+        let values = PluginKv::select_by_name(&self.db, plugin, name).await?;
+        Ok(values.into_iter().next().map(|kv| kv.value))
+    }
+
+    async fn set(&self, plugin: &str, name: &str, value: &str) -> anyhow::Result<()> {
+        self.db
+            .exec(
+                &DIALECT.upsert(
+                    "plugin_kv",
+                    &["plugin", "name", "value"],
+                    &["plugin", "name"],
+                    &["value"],
+                ),
+                vec![
+                    rbs::to_value!(plugin),
+                    rbs::to_value!(name),
+                    rbs::to_value!(value),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn remove(&self, plugin: &str, name: &str) -> anyhow::Result<()> {
+        self.db
+            .exec(
+                "DELETE FROM plugin_kv WHERE plugin = ? AND name = ?",
+                vec![rbs::to_value!(plugin), rbs::to_value!(name)],
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use rbatis::rbdc::datetime::DateTime;
+
+use crate::model::blacklist::Blacklist;
+use crate::model::div::UbiUser;
+use crate::model::keyword::KeyWord;
+use crate::model::plugin::DisabledPlugin;
+use crate::repository::{BlacklistRepo, DisabledPluginRepo, KeyWordRepo, KvRepo, UbiUserRepo};
+
+#[derive(Default)]
+pub struct MemoryKeyWordRepo {
+    keywords: Mutex<Vec<KeyWord>>,
+}
+
+#[async_trait]
+impl KeyWordRepo for MemoryKeyWordRepo {
+    async fn all(&self) -> anyhow::Result<Vec<KeyWord>> {
+        Ok(self.keywords.lock().unwrap().clone())
+    }
+
+    async fn get(&self, id: i64) -> anyhow::Result<Option<KeyWord>> {
+        let keywords = self.keywords.lock().unwrap();
+        Ok(keywords
+            .iter()
+            .find(|keyword| keyword.id == Some(id))
+            .cloned())
+    }
+
+    async fn add(&self, mut keyword: KeyWord) -> anyhow::Result<()> {
+        let mut keywords = self.keywords.lock().unwrap();
+        // one more than the largest id, like the autoincrement column
+        let id = keywords.iter().filter_map(|keyword| keyword.id).max();
+        keyword.id = Some(id.unwrap_or(0) + 1);
+        keywords.push(keyword);
+        Ok(())
+    }
+
+    async fn update(&self, keyword: KeyWord) -> anyhow::Result<()> {
+        let mut keywords = self.keywords.lock().unwrap();
+        if let Some(existing) = keywords
+            .iter_mut()
+            .find(|existing| existing.id == keyword.id)
+        {
+            *existing = keyword;
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, id: i64) -> anyhow::Result<()> {
+        let mut keywords = self.keywords.lock().unwrap();
+        keywords.retain(|keyword| keyword.id != Some(id));
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryUbiUserRepo {
+    users: Mutex<Vec<UbiUser>>,
+}
+
+#[async_trait]
+impl UbiUserRepo for MemoryUbiUserRepo {
+    async fn store_name(&self, id: &str, name: &str) -> anyhow::Result<()> {
+        let mut users = self.users.lock().unwrap();
+        if !users.iter().any(|user| user.id == id && user.name == name) {
+            users.push(UbiUser {
+                id: id.to_string(),
+                name: name.to_string(),
+                ts: DateTime::now(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn ids_by_name(&self, name: &str) -> anyhow::Result<Vec<String>> {
+        let users = self.users.lock().unwrap();
+        Ok(users
+            .iter()
+            .filter(|user| user.name.to_lowercase() == name.to_lowercase())
+            .map(|user| user.id.clone())
+            .collect())
+    }
+
+    async fn names_by_id(&self, id: &str) -> anyhow::Result<Vec<String>> {
+        let users = self.users.lock().unwrap();
+        Ok(users
+            .iter()
+            .filter(|user| user.id == id)
+            .map(|user| user.name.clone())
+            .collect())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryBlacklistRepo {
+    entries: Mutex<Vec<Blacklist>>,
+}
+
+#[async_trait]
+impl BlacklistRepo for MemoryBlacklistRepo {
+    async fn all(&self) -> anyhow::Result<Vec<Blacklist>> {
+        Ok(self.entries.lock().unwrap().clone())
+    }
+
+    async fn add(&self, group_id: i64, user_id: i64) -> anyhow::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if !entries
+            .iter()
+            .any(|entry| entry.group_id == group_id && entry.user_id == user_id)
+        {
+            entries.push(Blacklist { group_id, user_id });
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, group_id: i64, user_id: i64) -> anyhow::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|entry| entry.group_id != group_id || entry.user_id != user_id);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryDisabledPluginRepo {
+    disabled: Mutex<Vec<DisabledPlugin>>,
+}
+
+#[async_trait]
+impl DisabledPluginRepo for MemoryDisabledPluginRepo {
+    async fn all(&self) -> anyhow::Result<Vec<DisabledPlugin>> {
+        Ok(self.disabled.lock().unwrap().clone())
+    }
+
+    async fn disable(&self, group_id: i64, plugin: &str) -> anyhow::Result<()> {
+        let mut disabled = self.disabled.lock().unwrap();
+        if !disabled
+            .iter()
+            .any(|entry| entry.group_id == group_id && entry.plugin == plugin)
+        {
+            disabled.push(DisabledPlugin {
+                group_id,
+                plugin: plugin.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn enable(&self, group_id: i64, plugin: &str) -> anyhow::Result<()> {
+        let mut disabled = self.disabled.lock().unwrap();
+        disabled.retain(|entry| entry.group_id != group_id || entry.plugin != plugin);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryKvRepo {
+    values: Mutex<HashMap<(String, String), String>>,
+}
+
+#[async_trait]
+impl KvRepo for MemoryKvRepo {
+    async fn get(&self, plugin: &str, name: &str) -> anyhow::Result<Option<String>> {
+        let values = self.values.lock().unwrap();
+        Ok(values.get(&(plugin.to_string(), name.to_string())).cloned())
+    }
+
+    async fn set(&self, plugin: &str, name: &str, value: &str) -> anyhow::Result<()> {
+        let mut values = self.values.lock().unwrap();
+        values.insert((plugin.to_string(), name.to_string()), value.to_string());
+        Ok(())
+    }
+
+    async fn remove(&self, plugin: &str, name: &str) -> anyhow::Result<()> {
+        let mut values = self.values.lock().unwrap();
+        values.remove(&(plugin.to_string(), name.to_string()));
+        Ok(())
+    }
+}
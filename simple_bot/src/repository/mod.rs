@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use rbatis::RBatis;
+
+pub use blacklist::{BlacklistRepo, SqlBlacklistRepo};
+pub use disabled_plugin::{DisabledPluginRepo, SqlDisabledPluginRepo};
+pub use keyword::{KeyWordRepo, SqlKeyWordRepo};
+pub use kv::{KvRepo, SqlKvRepo};
+pub use ubi_user::{SqlUbiUserRepo, UbiUserRepo};
+
+mod blacklist;
+mod disabled_plugin;
+mod keyword;
+mod kv;
+mod memory;
+mod ubi_user;
+
+// every table the bot works with, behind traits so it can also run without a database
+#[derive(Clone)]
+pub struct Repos {
+    pub keywords: Arc<dyn KeyWordRepo>,
+    pub ubi_users: Arc<dyn UbiUserRepo>,
+    pub blacklist: Arc<dyn BlacklistRepo>,
+    pub disabled_plugins: Arc<dyn DisabledPluginRepo>,
+    pub kv: Arc<dyn KvRepo>,
+}
+
+impl Repos {
+    pub fn sql(db: RBatis) -> Self {
+        Repos {
+            keywords: Arc::new(SqlKeyWordRepo::new(db.clone())),
+            ubi_users: Arc::new(SqlUbiUserRepo::new(db.clone())),
+            blacklist: Arc::new(SqlBlacklistRepo::new(db.clone())),
+            disabled_plugins: Arc::new(SqlDisabledPluginRepo::new(db.clone())),
+            kv: Arc::new(SqlKvRepo::new(db)),
+        }
+    }
+
+    // empty tables kept in memory, for `--memory` and for testing plugins without a database
+    pub fn memory() -> Self {
+        Repos {
+            keywords: Arc::new(memory::MemoryKeyWordRepo::default()),
+            ubi_users: Arc::new(memory::MemoryUbiUserRepo::default()),
+            blacklist: Arc::new(memory::MemoryBlacklistRepo::default()),
+            disabled_plugins: Arc::new(memory::MemoryDisabledPluginRepo::default()),
+            kv: Arc::new(memory::MemoryKvRepo::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::keyword::KeyWord;
+
+    fn keyword(regex: &str) -> KeyWord {
+        KeyWord {
+            id: None,
+            group_id: 1,
+            regex: Some(regex.to_string()),
+            reply: Some("reply".to_string()),
+            chance: 100,
+            enabled: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn keyword_ids_follow_the_largest() {
+        let repos = Repos::memory();
+        repos.keywords.add(keyword("a")).await.unwrap();
+        repos.keywords.add(keyword("b")).await.unwrap();
+        repos.keywords.remove(1).await.unwrap();
+        // whatever id the caller set is replaced
+        repos.keywords.add(KeyWord { id: Some(1), ..keyword("c") }).await.unwrap();
+        let keywords = repos.keywords.all().await.unwrap();
+        let ids = keywords.iter().map(|keyword| keyword.id).collect::<Vec<_>>();
+        assert_eq!(ids, [Some(2), Some(3)]);
+    }
+
+    #[tokio::test]
+    async fn keyword_update_and_remove() {
+        let repos = Repos::memory();
+        repos.keywords.add(keyword("a")).await.unwrap();
+        let mut stored = repos.keywords.get(1).await.unwrap().unwrap();
+        stored.enabled = 0;
+        repos.keywords.update(stored.clone()).await.unwrap();
+        assert!(repos.keywords.get(1).await.unwrap() == Some(stored));
+        // an unknown id adds nothing
+        repos.keywords.update(KeyWord { id: Some(9), ..keyword("b") }).await.unwrap();
+        assert_eq!(repos.keywords.all().await.unwrap().len(), 1);
+        repos.keywords.remove(1).await.unwrap();
+        assert!(repos.keywords.get(1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn ubi_user_names_ignore_case() {
+        let repos = Repos::memory();
+        repos.ubi_users.store_name("id1", "Agent").await.unwrap();
+        repos.ubi_users.store_name("id1", "Agent").await.unwrap();
+        repos.ubi_users.store_name("id2", "AGENT").await.unwrap();
+        repos.ubi_users.store_name("id1", "Rogue").await.unwrap();
+        assert_eq!(repos.ubi_users.ids_by_name("agent").await.unwrap(), ["id1", "id2"]);
+        assert_eq!(repos.ubi_users.names_by_id("id1").await.unwrap(), ["Agent", "Rogue"]);
+        assert!(repos.ubi_users.ids_by_name("age").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn blacklist_add_twice_and_remove() {
+        let repos = Repos::memory();
+        repos.blacklist.add(1, 10).await.unwrap();
+        repos.blacklist.add(1, 10).await.unwrap();
+        repos.blacklist.add(2, 10).await.unwrap();
+        repos.blacklist.remove(1, 10).await.unwrap();
+        let entries = repos.blacklist.all().await.unwrap();
+        let entries = entries
+            .iter()
+            .map(|entry| (entry.group_id, entry.user_id))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [(2, 10)]);
+    }
+
+    #[tokio::test]
+    async fn disabled_plugins_by_group() {
+        let repos = Repos::memory();
+        repos.disabled_plugins.disable(1, "KeyWord").await.unwrap();
+        repos.disabled_plugins.disable(1, "KeyWord").await.unwrap();
+        repos.disabled_plugins.disable(1, "Repeater").await.unwrap();
+        repos.disabled_plugins.enable(2, "Repeater").await.unwrap();
+        repos.disabled_plugins.enable(1, "KeyWord").await.unwrap();
+        let disabled = repos.disabled_plugins.all().await.unwrap();
+        let disabled = disabled
+            .iter()
+            .map(|entry| (entry.group_id, entry.plugin.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(disabled, [(1, "Repeater")]);
+    }
+
+    #[tokio::test]
+    async fn kv_values_per_plugin() {
+        let repos = Repos::memory();
+        repos.kv.set("Remind", "pending", "[]").await.unwrap();
+        repos.kv.set("Remind", "pending", "[1]").await.unwrap();
+        repos.kv.set("Div", "pending", "{}").await.unwrap();
+        assert_eq!(repos.kv.get("Remind", "pending").await.unwrap().as_deref(), Some("[1]"));
+        repos.kv.remove("Remind", "pending").await.unwrap();
+        assert!(repos.kv.get("Remind", "pending").await.unwrap().is_none());
+        assert_eq!(repos.kv.get("Div", "pending").await.unwrap().as_deref(), Some("{}"));
+    }
+}
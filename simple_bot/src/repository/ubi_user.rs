@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+use rbatis::RBatis;
+
+use crate::database::DIALECT;
+use crate::model::div::UbiUser;
+
+// the names seen for each Ubisoft account
+#[async_trait]
+pub trait UbiUserRepo: Send + Sync {
+    async fn store_name(&self, id: &str, name: &str) -> anyhow::Result<()>;
+
+    // ignoring case, a name may have belonged to several accounts
+    async fn ids_by_name(&self, name: &str) -> anyhow::Result<Vec<String>>;
+
+    async fn names_by_id(&self, id: &str) -> anyhow::Result<Vec<String>>;
+}
+
+pub struct SqlUbiUserRepo {
+    db: RBatis,
+}
+
+impl SqlUbiUserRepo {
+    pub fn new(db: RBatis) -> Self {
+        SqlUbiUserRepo { db }
+    }
+}
+
+#[async_trait]
+impl UbiUserRepo for SqlUbiUserRepo {
+    async fn store_name(&self, id: &str, name: &str) -> anyhow::Result<()> {
+        self.db
+            .exec(
+                &DIALECT.insert_ignore("ubi_user", &["id", "name"]),
+                vec![rbs::to_value!(id), rbs::to_value!(name)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn ids_by_name(&self, name: &str) -> anyhow::Result<Vec<String>> {
+        let users = UbiUser::select_by_name(&self.db, name).await?;
+        Ok(users.into_iter().map(|user| user.id).collect())
+    }
+
+    async fn names_by_id(&self, id: &str) -> anyhow::Result<Vec<String>> {
+        let users = UbiUser::select_by_id(&self.db, id).await?;
+        Ok(users.into_iter().map(|user| user.name).collect())
+    }
+}
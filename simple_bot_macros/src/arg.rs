@@ -1,53 +1,74 @@
-use std::collections::HashSet;
-use std::ops::Deref;
+use std::collections::HashMap;
 
-use syn::parse::{Parse, ParseStream};
+use simple_bot_syntax::{parse_pattern, OptionSpec, Placeholder, Segment, Syntax};
 
-#[derive(Debug)]
-pub struct Arg(Vec<String>);
+// the placeholders and options of a pattern, parsed by the same rules as at runtime
+pub struct Arg {
+    syntax: Syntax,
+}
 
-impl Deref for Arg {
-    type Target = Vec<String>;
+impl Arg {
+    pub fn do_parse(lit_str: &syn::LitStr) -> syn::Result<Self> {
+        let syntax = parse_pattern(&lit_str.value())
+            .map_err(|e| syn::Error::new(lit_str.span(), e.to_string()))?;
+        Ok(Arg { syntax })
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    pub fn placeholders(&self) -> &[Placeholder] {
+        &self.syntax.placeholders
     }
-}
 
-impl Parse for Arg {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let input = input.parse::<proc_macro2::TokenStream>()?;
-        Self::do_parse(&input)
+    pub fn options(&self) -> &[OptionSpec] {
+        &self.syntax.options
     }
-}
 
-impl Arg {
-    pub fn do_parse(token_stream: &proc_macro2::TokenStream) -> syn::Result<Self> {
-        let buf = token_stream.clone().into_iter().collect::<Vec<_>>();
-        let mut args = Vec::new();
-        let mut args_set = HashSet::new();
-        for i in 0..buf.len() {
-            if let proc_macro2::TokenTree::Group(group) = &buf[i] {
-                let buffer = syn::buffer::TokenBuffer::new2(group.stream());
-                let cursor = buffer.begin();
-                if let Some((ident, next)) = cursor.ident() {
-                    if !args_set.insert(ident.to_string()) {
-                        return Err(syn::Error::new(
-                            cursor.span(),
-                            format!(r#"duplicate placeholders found: "{}""#, ident),
-                        ));
-                    }
+    pub fn placeholder(&self, name: &str) -> Option<&Placeholder> {
+        self.placeholders().iter().find(|p| p.name == name)
+    }
+
+    pub fn option(&self, ident: &str) -> Option<&OptionSpec> {
+        self.options().iter().find(|o| o.ident() == ident)
+    }
 
-                    if !next.eof() {
-                        return Err(syn::Error::new(
-                            next.span(),
-                            format!(r#"something else besides placeholder: "{}""#, ident),
-                        ));
+    // the pattern with the type of each parameter, like "/keyword list [page: u32]",
+    // `types` maps a parameter to its type and whether it is required
+    pub fn signature(&self, types: &HashMap<String, (String, bool)>) -> String {
+        let mut signature = String::new();
+        for segment in &self.syntax.segments {
+            let placeholder = match segment {
+                Segment::Literal(literal) => {
+                    signature.push_str(literal);
+                    continue;
+                }
+                Segment::Placeholder(i) => &self.syntax.placeholders[*i],
+            };
+            let (name, constraint) = match placeholder.spec.split_once(':') {
+                Some((name, constraint)) => (name, Some(constraint)),
+                None => (placeholder.spec.as_str(), None),
+            };
+            signature.push_str(&match types.get(&placeholder.name) {
+                // a choice says more than the String it is parsed into
+                Some((ty, required)) => {
+                    let ty = match constraint {
+                        Some(constraint) if ty == "String" => constraint,
+                        _ => ty,
+                    };
+                    match required {
+                        true => format!("<{}: {}>", name, ty),
+                        false => format!("[{}: {}]", name, ty),
                     }
-                    args.push(ident.to_string());
                 }
+                None => format!("<{}>", placeholder.spec),
+            });
+        }
+        for option in self.options() {
+            match &option.value_type {
+                Some(value_type) => {
+                    signature.push_str(&format!(" [--{}=<{}>]", option.name, value_type))
+                }
+                None => signature.push_str(&format!(" [--{}]", option.name)),
             }
         }
-        Ok(Arg(args))
+        signature
     }
 }
@@ -1,198 +1,606 @@
+use std::collections::{HashMap, HashSet};
+
 use proc_macro::TokenStream;
 
 use quote::{quote, ToTokens};
+use syn::spanned::Spanned;
 use syn::{parse_macro_input, Ident, ItemFn};
 
 use meta::Meta;
+use plugin::PluginMeta;
 
 mod arg;
 mod meta;
+mod plugin;
 
 #[proc_macro_attribute]
 pub fn action(meta: TokenStream, input: TokenStream) -> TokenStream {
     let meta = parse_macro_input!(meta as Meta);
     let function = parse_macro_input!(input as ItemFn);
+    expand_action(meta, function)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_action(meta: Meta, function: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
     let params = &function.sig.inputs;
     let function_name = &function.sig.ident;
-    let mut param_infos = Vec::new();
+    if function.sig.asyncness.is_none() {
+        return Err(syn::Error::new(
+            function.sig.fn_token.span(),
+            "an action must be an async fn",
+        ));
+    }
+    let mut conversions = Vec::new();
+    let mut call_args = Vec::new();
+    let mut types = HashMap::new();
+    let mut elements = Vec::new();
+    let mut optional = Vec::new();
+    let mut has_event = false;
+    let mut has_context = false;
+    // placeholders and options taken by a parameter
+    let mut bound = HashSet::new();
     for param in params {
-        match param {
-            syn::FnArg::Receiver(_) => continue,
-            syn::FnArg::Typed(pt) => {
-                let param_pat = pt.pat.as_ref();
-                let param_ty = pt.ty.as_ref();
-                let param_name = param_pat.to_token_stream().to_string();
-                let param_type = match param_ty {
-                    syn::Type::Path(tp) => {
-                        // normal paran type
-                        let last_segment = tp.path.segments.last().unwrap();
-                        let mut param_type = last_segment.ident.to_string();
-                        if param_type != "Option" {
-                            unimplemented!("type {} must wrapped in option", param_type);
-                        }
-                        param_type = match &last_segment.arguments {
-                            syn::PathArguments::AngleBracketed(arg) => {
-                                arg.args.last().unwrap().to_token_stream().to_string()
-                            }
-                            _ => {
-                                unimplemented!();
-                            }
-                        };
-                        param_type
-                    }
-                    syn::Type::Reference(_) => {
-                        // there is only one reference, it is a reference of MessageEvent
-                        // use an empty string to mark special handling
-                        String::default()
-                    }
-                    _ => {
-                        unimplemented!("not supported type")
-                    }
+        let pt = match param {
+            syn::FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new(receiver.span(), "an action cannot take self"))
+            }
+            syn::FnArg::Typed(pt) => pt,
+        };
+        let param_ident = match pt.pat.as_ref() {
+            syn::Pat::Ident(pat_ident) => pat_ident.ident.clone(),
+            pat => return Err(syn::Error::new(pat.span(), "expected a plain parameter name")),
+        };
+        let param_name = param_ident.to_string();
+        let param_ty = pt.ty.as_ref();
+        let tp = match param_ty {
+            syn::Type::Reference(reference) => {
+                // a reference is either the PluginContext or the MessageEvent
+                let is_context = match reference.elem.as_ref() {
+                    syn::Type::Path(tp) => tp
+                        .path
+                        .segments
+                        .last()
+                        .is_some_and(|segment| segment.ident == "PluginContext"),
+                    _ => false,
                 };
-                param_infos.push((param_name, param_type));
+                let (seen, name) = if is_context {
+                    (&mut has_context, "&PluginContext")
+                } else {
+                    (&mut has_event, "&MessageEvent")
+                };
+                if *seen {
+                    return Err(syn::Error::new(
+                        param_ty.span(),
+                        format!("an action takes at most one {}", name),
+                    ));
+                }
+                *seen = true;
+                call_args.push(if is_context {
+                    quote! { context }
+                } else {
+                    quote! { event }
+                });
+                continue;
+            }
+            syn::Type::Path(tp) => tp,
+            _ => return Err(syn::Error::new(param_ty.span(), "not supported type")),
+        };
+        let option = meta.args.option(&param_name);
+        if let Some(option) = option {
+            bound.insert(param_name.clone());
+            if option.value_type.is_none() {
+                // a `[--flag]` of the pattern, true when it is given
+                if !tp.path.is_ident("bool") {
+                    return Err(syn::Error::new(param_ty.span(), "a flag must be a bool"));
+                }
+                conversions.push(quote! {
+                    let #param_ident = args.flag(#param_name);
+                });
+                call_args.push(quote! { #param_ident });
+                continue;
             }
         }
-    }
-    let mut invoke_builder = String::new();
-    for i in 0..param_infos.len() {
-        let param_name: &String = &param_infos[i].0;
-        let param_type: &String = &param_infos[i].1;
-        match param_type.as_str() {
-            "RQElem" => {
-                invoke_builder.push_str(&format!(
-                    r#"element_map.remove(&"{}".to_string()),"#,
-                    param_name
+        // Option<T> is optional and T is required
+        let (inner_ty, required) = split_option(param_ty, tp)?;
+        let param_type = match &inner_ty {
+            syn::Type::Path(tp) => tp.path.segments.last().unwrap().ident.to_string(),
+            _ => inner_ty.to_token_stream().to_string(),
+        };
+        if !SCALAR_TYPES.contains(&param_type.as_str())
+            && element_kind(&param_type).is_none()
+            && param_type != "Reply"
+        {
+            return Err(syn::Error::new(
+                inner_ty.span(),
+                format!(
+                    "unsupported parameter type `{}`, expected String, a number, bool, char, \
+                     At, GroupImage, FriendImage, Face, RQElem or Reply",
+                    param_type
+                ),
+            ));
+        }
+        if let Some(option) = option {
+            let value_type = option.value_type.as_deref().unwrap_or_default();
+            if required || param_type != value_type {
+                return Err(syn::Error::new(
+                    param_ty.span(),
+                    format!(
+                        "option \"--{}\" is declared as <{}>, expected Option<{}>",
+                        option.name, value_type, value_type
+                    ),
                 ));
             }
-            "String" => {
-                invoke_builder.push_str(&format!(
-                    r#"text_map.remove(&"{}".to_string()),"#,
-                    param_name
+        } else if param_type == "Reply" {
+            if meta.args.placeholder(&param_name).is_some() {
+                return Err(syn::Error::new(
+                    param_ident.span(),
+                    "a Reply is the message quoted by the command and takes no placeholder",
                 ));
             }
-            "" => {
-                invoke_builder.push_str(&format!("{},", param_name));
-            }
-            _ => {
-                invoke_builder.push_str(&format!(
-                    r#"text_map.remove(&"{}".to_string()).and_then(|v| v.parse().ok()),"#,
-                    param_name
+        } else {
+            let placeholder = meta.args.placeholder(&param_name).ok_or_else(|| {
+                syn::Error::new(
+                    param_ident.span(),
+                    format!("parameter `{}` has no placeholder in the pattern", param_name),
+                )
+            })?;
+            bound.insert(param_name.clone());
+            if placeholder.greedy && param_type != "String" {
+                return Err(syn::Error::new(
+                    inner_ty.span(),
+                    format!(
+                        "\"{{{}..}}\" takes the rest of the message, expected a String",
+                        param_name
+                    ),
                 ));
             }
+            if let Some(kind) = element_kind(&param_type) {
+                if placeholder.constraint.is_some() {
+                    return Err(syn::Error::new(
+                        param_ident.span(),
+                        format!("placeholder \"{}\" takes an element and cannot have a constraint", param_name),
+                    ));
+                }
+                let kind = Ident::new(kind, param_ty.span());
+                elements.push(quote! {
+                    (#param_name, crate::plugin::pattern::ElementKind::#kind)
+                });
+            }
+            if !required {
+                optional.push(param_name.clone());
+            }
+        }
+        types.insert(param_name.clone(), (param_type.clone(), required));
+        conversions.push(convert(
+            &param_name,
+            &param_ident,
+            &inner_ty,
+            &param_type,
+            required,
+        ));
+        call_args.push(quote! { #param_ident });
+    }
+    for placeholder in meta.args.placeholders() {
+        if !bound.contains(&placeholder.name) {
+            return Err(syn::Error::new(
+                meta.span,
+                format!("placeholder \"{}\" has no matching parameter", placeholder.name),
+            ));
+        }
+    }
+    for option in meta.args.options() {
+        if !bound.contains(&option.ident()) {
+            return Err(syn::Error::new(
+                meta.span,
+                format!(
+                    "option \"--{}\" has no matching parameter `{}`",
+                    option.name, option.ident()
+                ),
+            ));
         }
     }
 
-    let invoke: syn::Expr =
-        syn::parse_str(&format!("{}({}).await", function_name, invoke_builder)).unwrap();
+    let context_param = if has_context {
+        quote! { context }
+    } else {
+        quote! { _context }
+    };
+    let args_param = if conversions.is_empty() {
+        quote! { _args }
+    } else {
+        quote! { mut args }
+    };
+    let invoke = quote! {
+        #(#conversions)*
+        #function_name(#(#call_args),*).await
+    };
     let pattern = meta.pattern.to_token_stream();
-    let args_json = serde_json::to_string(&meta.args).unwrap();
-    let param_infos_json = serde_json::to_string(&param_infos).unwrap();
+    let name = function_name.to_string();
+    let usage = meta.args.signature(&types);
+    // the doc comment of the function describes the action
+    let desc = function
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(doc),
+                        ..
+                    }),
+                ..
+            }) => Some(doc.value().trim().to_string()),
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let desc_fn = (!desc.is_empty()).then(|| {
+        quote! {
+            fn get_desc(&self) -> &str {
+                #desc
+            }
+        }
+    });
+    let rate_limit_fn = if meta.user_limit.is_some() || meta.group_limit.is_some() {
+        let to_rate = |limit: Option<(usize, u64)>| match limit {
+            Some((count, secs)) => quote! {
+                Some(crate::plugin::limit::Rate {
+                    count: #count,
+                    period: std::time::Duration::from_secs(#secs),
+                })
+            },
+            None => quote! { None },
+        };
+        let user = to_rate(meta.user_limit);
+        let group = to_rate(meta.group_limit);
+        Some(quote! {
+            fn get_rate_limit(&self) -> crate::plugin::limit::RateLimit {
+                crate::plugin::limit::RateLimit {
+                    user: #user,
+                    group: #group,
+                }
+            }
+        })
+    } else {
+        None
+    };
+    let role_fn = meta.role.as_ref().map(|role| {
+        quote! {
+            fn get_role(&self) -> crate::plugin::Role {
+                crate::plugin::Role::#role
+            }
+        }
+    });
     let dispatcher_function_name = Ident::new(
         &(function_name.to_string() + "_dispatcher"),
         function_name.span(),
     );
-    let action_name = Ident::new(
-        &format!(
-            "{}Action",
-            function_name.to_string()[0..1].to_uppercase() + &function_name.to_string()[1..1]
-        ),
-        function_name.span(),
-    );
+    // add_keyword => AddKeywordAction
+    let action_name = function_name
+        .to_string()
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<String>();
+    let action_name = Ident::new(&format!("{}Action", action_name), function_name.span());
     let action_impl = quote! {
         struct #action_name {
-            pattern: String
+            pattern: crate::plugin::pattern::Pattern
         }
 
         #[proc_qq::re_exports::async_trait::async_trait]
         impl crate::plugin::Action for #action_name {
-            async fn do_action(&self, event: &proc_qq::MessageEvent, slot_content: Vec<String>) -> anyhow::Result<bool> {
-                let args_json = #args_json;
-                let param_infos_json = #param_infos_json;
-                let args = serde_json::from_str::<std::collections::HashMap<usize, String>>(&args_json).expect("args deserialize error");
-                let param_infos = serde_json::from_str::<Vec<(String, String)>>(&param_infos_json).expect("param_name_and_type deserialize error");
-                if param_infos.len() - 1 != slot_content.len() {
-                    return Ok(false);
-                }
-                let message_chain = <proc_qq::MessageEvent as proc_qq::MessageChainPointTrait>::message_chain(event);
-                let elements = message_chain.clone().into_iter().filter(
-                    |e| {
-                        match e{
-                            proc_qq::re_exports::ricq_core::msg::elem::RQElem::Other(_) => false,
-                            _ => true
-                        }
-                    }
-                ).collect::<Vec<proc_qq::re_exports::ricq_core::msg::elem::RQElem>>();
-                let mut text_map = std::collections::HashMap::new();
-                let mut element_map = std::collections::HashMap::new();
-                // point to slot_content / param_name_and_type
-                let mut i = 0;
-                // point to message_chain elements
-                let mut j = 0;
-                // offset due to MessageEvent
-                let mut offset = 0;
-                while i < param_infos.len() {
-                    // found a MessageEvent
-                    if param_infos[i].1 == "" {
-                        i += 1;
-                        offset += 1;
-                        continue;
-                    }
-                    match elements[j] {
-                        proc_qq::re_exports::ricq_core::msg::elem::RQElem::Text(_) => {
-                            while i < param_infos.len() && slot_content[i - offset] != "" {
-                                // found a MessageEvent
-                                if param_infos[i].1 == "" {
-                                    i += 1;
-                                    offset += 1;
-                                    continue;
-                                }
-                                let need_name = &param_infos[i].0;
-                                let need_type = &param_infos[i].1;
-                                text_map.insert(need_name.clone(), slot_content[i - offset].clone());
-                                i += 1;
-                            }
-                            j += 1;
-                        },
-                        _ => {
-                            let need_name = &param_infos[i].0;
-                            let need_type = &param_infos[i].1;
-                            element_map.insert(need_name.clone(), elements[i].clone());
-                            i += 1;
-                            j += 1;
-                        }
-                    }
-                }
+            async fn do_action(&self, event: &proc_qq::MessageEvent, #context_param: &crate::plugin::PluginContext, #args_param: crate::plugin::pattern::Args) -> anyhow::Result<bool> {
                 #invoke
             }
 
-            fn get_pattern(&self) -> String {
-                self.pattern.clone()
+            fn get_pattern(&self) -> &crate::plugin::pattern::Pattern {
+                &self.pattern
             }
+
+            fn get_name(&self) -> &str {
+                #name
+            }
+
+            fn get_usage(&self) -> String {
+                #usage.to_string()
+            }
+
+            #desc_fn
+
+            #role_fn
+
+            #rate_limit_fn
         }
     };
+    let with_elements = (!elements.is_empty()).then(|| {
+        quote! { .with_elements(&[#(#elements),*])? }
+    });
+    let with_optional = (!optional.is_empty()).then(|| {
+        quote! { .with_optional(&[#(#optional),*])? }
+    });
     let dispatcher_function = quote! {
-        fn #dispatcher_function_name() -> Box<dyn crate::plugin::Action> {
-            Box::new(
+        fn #dispatcher_function_name() -> anyhow::Result<Box<dyn crate::plugin::Action>> {
+            Ok(Box::new(
                 #action_name {
-                    pattern: #pattern.to_string()
+                    pattern: crate::plugin::pattern::Pattern::new(#pattern)?
+                        #with_elements
+                        #with_optional
                 }
-            )
+            ))
         }
     };
-    let gen_code = quote! {
+    // picked up by the `#[plugin]` of the same module
+    let registration = quote! {
+        inventory::submit! {
+            crate::plugin::ActionRegistration {
+                module: module_path!(),
+                line: line!(),
+                create: #dispatcher_function_name,
+            }
+        }
+    };
+    Ok(quote! {
         #function
         #action_impl
         #dispatcher_function
-    };
-    TokenStream::from(gen_code)
+        #registration
+    })
+}
+
+// turns `pub struct Remind;` into a command plugin holding every `#[action]` of its module,
+// the plugin registers itself so it needs no entry in `crate::plugin::internal`
+#[proc_macro_attribute]
+pub fn plugin(meta: TokenStream, input: TokenStream) -> TokenStream {
+    let meta = parse_macro_input!(meta as PluginMeta);
+    let item = parse_macro_input!(input as syn::ItemStruct);
+    expand_plugin(meta, item)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
 
-#[proc_macro]
-pub fn make_action(input: TokenStream) -> TokenStream {
-    let origin_function_name = parse_macro_input!(input as Ident);
-    let dispatcher_function_name = origin_function_name.to_string() + "_dispatcher";
-    let ident = Ident::new(&dispatcher_function_name, origin_function_name.span());
-    TokenStream::from(quote! {
-        #ident()
+fn expand_plugin(meta: PluginMeta, item: syn::ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
+    if !matches!(item.fields, syn::Fields::Unit) || !item.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            item.span(),
+            "a plugin must be a unit struct like `pub struct Remind;`",
+        ));
+    }
+    let syn::ItemStruct {
+        attrs, vis, ident, ..
+    } = &item;
+    let PluginMeta {
+        name, desc, root, ..
+    } = &meta;
+    let id = meta
+        .id
+        .clone()
+        .unwrap_or_else(|| syn::LitStr::new(&ident.to_string(), ident.span()));
+    let root_fn = root.as_ref().map(|root| {
+        quote! {
+            fn get_root(&self) -> Option<&str> {
+                Some(#root)
+            }
+        }
+    });
+    let on_load_fn = meta.on_load.as_ref().map(|on_load| {
+        quote! {
+            async fn on_load(&self, context: &crate::plugin::PluginContext) -> anyhow::Result<()> {
+                #on_load(context).await
+            }
+        }
+    });
+    let on_shutdown_fn = meta.on_shutdown.as_ref().map(|on_shutdown| {
+        quote! {
+            async fn on_shutdown(&self, context: &crate::plugin::PluginContext) -> anyhow::Result<()> {
+                #on_shutdown(context).await
+            }
+        }
+    });
+    let on_reload_fn = meta.on_reload.as_ref().map(|on_reload| {
+        quote! {
+            async fn on_reload(&self, context: &crate::plugin::PluginContext) -> anyhow::Result<()> {
+                #on_reload(context).await
+            }
+        }
+    });
+    let config_fn = meta.config.as_ref().map(|config| {
+        quote! {
+            fn get_config_spec(&self) -> Option<crate::plugin::ConfigSpec> {
+                Some(crate::plugin::ConfigSpec::of::<#config>())
+            }
+        }
+    });
+    let roles = &meta.roles;
+    let priority_fn = meta.priority.map(|priority| {
+        quote! {
+            fn get_priority(&self) -> i32 {
+                #priority
+            }
+        }
+    });
+    Ok(quote! {
+        #(#attrs)*
+        #vis struct #ident {
+            actions: Vec<Box<dyn crate::plugin::Action>>,
+        }
+
+        impl #ident {
+            pub fn new() -> Self {
+                #ident {
+                    actions: crate::plugin::module_actions(module_path!()),
+                }
+            }
+        }
+
+        #[proc_qq::re_exports::async_trait::async_trait]
+        impl crate::plugin::Plugin for #ident {
+            fn get_id(&self) -> &str {
+                #id
+            }
+
+            fn get_name(&self) -> &str {
+                #name
+            }
+
+            fn get_desc(&self) -> &str {
+                #desc
+            }
+
+            #priority_fn
+
+            #config_fn
+
+            #on_load_fn
+
+            #on_shutdown_fn
+
+            #on_reload_fn
+        }
+
+        impl crate::plugin::CommandPlugin for #ident {
+            fn get_actions(&self) -> &Vec<Box<dyn crate::plugin::Action>> {
+                &self.actions
+            }
+
+            #root_fn
+        }
+
+        inventory::submit! {
+            crate::plugin::PluginRegistration(|| {
+                let plugin = crate::plugin::instance(#ident::new());
+                crate::plugin::Roles::new(plugin).command(plugin)#(.#roles(plugin))*
+            })
+        }
     })
 }
+
+// the types parsed from the text of a placeholder
+const SCALAR_TYPES: [&str; 17] = [
+    "String", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128",
+    "usize", "f32", "f64", "bool", "char",
+];
+
+// Option<T> => (T, false), T => (T, true)
+fn split_option(param_ty: &syn::Type, tp: &syn::TypePath) -> syn::Result<(syn::Type, bool)> {
+    let last_segment = match tp.path.segments.last() {
+        Some(last_segment) if last_segment.ident == "Option" => last_segment,
+        _ => return Ok((param_ty.clone(), true)),
+    };
+    match &last_segment.arguments {
+        syn::PathArguments::AngleBracketed(arg) => match arg.args.last() {
+            Some(syn::GenericArgument::Type(ty)) => Ok((ty.clone(), false)),
+            _ => Err(syn::Error::new(param_ty.span(), "expected a type inside Option")),
+        },
+        _ => Err(syn::Error::new(param_ty.span(), "expected a type inside Option")),
+    }
+}
+
+fn describe_type(param_type: &str) -> &str {
+    match param_type {
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => "整数",
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => "非负整数",
+        "f32" | "f64" => "小数",
+        "bool" => "true或false",
+        other => other,
+    }
+}
+
+// the kind of element a parameter of this type takes, see `crate::plugin::pattern::ElementKind`
+fn element_kind(param_type: &str) -> Option<&'static str> {
+    match param_type {
+        "At" => Some("At"),
+        "GroupImage" | "FriendImage" => Some("Image"),
+        "Face" => Some("Face"),
+        "RQElem" => Some("Any"),
+        _ => None,
+    }
+}
+
+fn describe_element(param_type: &str) -> &str {
+    match param_type {
+        "At" => "@某人",
+        "GroupImage" => "群图片",
+        "FriendImage" => "好友图片",
+        "Face" => "表情",
+        other => other,
+    }
+}
+
+// turn the captured text of a placeholder into the parameter, an invalid or a missing
+// required value ends the action with a reply before the function body runs
+fn convert(
+    param_name: &str,
+    param_ident: &Ident,
+    ty: &syn::Type,
+    param_type: &str,
+    required: bool,
+) -> proc_macro2::TokenStream {
+    let parse = if param_type == "String" {
+        quote! { Some(value) }
+    } else {
+        let expected = describe_type(param_type);
+        quote! {
+            match value.trim().parse::<#ty>() {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    return Err(crate::plugin::UserError::invalid_argument(
+                        #param_name, &value, #expected, self.pattern.usage(),
+                    )
+                    .into())
+                }
+            }
+        }
+    };
+    let value = match param_type {
+        "Reply" => quote! { args.take_reply() },
+        "RQElem" => quote! { args.take_element(#param_name) },
+        "At" | "GroupImage" | "FriendImage" | "Face" => {
+            let variant = Ident::new(param_type, param_ident.span());
+            let expected = describe_element(param_type);
+            quote! {
+                match args.take_element(#param_name) {
+                    Some(proc_qq::re_exports::ricq_core::msg::elem::RQElem::#variant(value)) => Some(value),
+                    Some(_) => {
+                        return Err(crate::plugin::UserError::invalid_argument(
+                            #param_name, "其他消息元素", #expected, self.pattern.usage(),
+                        )
+                        .into())
+                    }
+                    None => None,
+                }
+            }
+        }
+        _ => quote! {
+            match args
+                .take(#param_name)
+                .filter(|value: &String| !value.trim().is_empty())
+            {
+                Some(value) => #parse,
+                None => None,
+            }
+        },
+    };
+    if required {
+        quote! {
+            let #param_ident = match #value {
+                Some(value) => value,
+                None => {
+                    return Err(crate::plugin::UserError::missing_argument(#param_name, self.pattern.usage()).into())
+                }
+            };
+        }
+    } else {
+        quote! {
+            let #param_ident = #value;
+        }
+    }
+}
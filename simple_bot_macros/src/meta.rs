@@ -1,27 +1,75 @@
-use std::collections::HashMap;
-
 use syn::parse::{Parse, ParseStream};
 
 use crate::arg::Arg;
 
+const ROLES: [&str; 4] = ["Blacklisted", "Member", "GroupAdmin", "Owner"];
+
 pub struct Meta {
     pub pattern: String,
-    pub args: HashMap<usize, String>,
+    // where the pattern is, for errors about it
+    pub span: proc_macro2::Span,
+    pub args: Arg,
+    pub role: Option<syn::Ident>,
+    // (count, period in seconds)
+    pub user_limit: Option<(usize, u64)>,
+    pub group_limit: Option<(usize, u64)>,
 }
 
 impl Parse for Meta {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let lit_str = input.parse::<syn::LitStr>()?;
         let pattern = lit_str.value();
-        let stream = pattern
-            .parse::<proc_macro2::TokenStream>()
-            .map_err(|e| syn::Error::new(lit_str.span(), e.to_string()))?;
-        let args = syn::parse2::<Arg>(stream).map(|args| {
-            args.clone()
-                .into_iter()
-                .enumerate()
-                .collect::<HashMap<_, _>>()
-        })?;
-        Ok(Meta { pattern, args })
+        let args = Arg::do_parse(&lit_str)?;
+        let mut role = None;
+        let mut user_limit = None;
+        let mut group_limit = None;
+        while !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let key = input.parse::<syn::Ident>()?;
+            input.parse::<syn::Token![=]>()?;
+            match key.to_string().as_str() {
+                "role" => {
+                    let value = input.parse::<syn::Ident>()?;
+                    if !ROLES.contains(&value.to_string().as_str()) {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            format!(r#"unknown role: "{}", expected one of {:?}"#, value, ROLES),
+                        ));
+                    }
+                    role = Some(value);
+                }
+                "user_limit" => user_limit = Some(parse_rate(&input.parse::<syn::LitStr>()?)?),
+                "group_limit" => group_limit = Some(parse_rate(&input.parse::<syn::LitStr>()?)?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!(r#"unknown option: "{}""#, key),
+                    ))
+                }
+            }
+        }
+        Ok(Meta {
+            pattern,
+            span: lit_str.span(),
+            args,
+            role,
+            user_limit,
+            group_limit,
+        })
     }
 }
+
+// the grammar of `crate::plugin::limit::Rate`: "count/period" like "1/30s" or "5/1m"
+fn parse_rate(lit_str: &syn::LitStr) -> syn::Result<(usize, u64)> {
+    let value = lit_str.value();
+    let (count, period) = simple_bot_syntax::parse_rate(&value).map_err(|_| {
+        syn::Error::new(
+            lit_str.span(),
+            format!(r#"invalid rate: "{}", expected "count/period" like "1/30s""#, value),
+        )
+    })?;
+    Ok((count, period.as_secs()))
+}
@@ -0,0 +1,105 @@
+use syn::parse::{Parse, ParseStream};
+
+// the roles a plugin may implement besides `CommandPlugin`, see `crate::plugin::Roles`
+const ROLES: [&str; 4] = ["raw", "scheduled", "notice", "request"];
+
+pub struct PluginMeta {
+    // see `crate::plugin::Plugin::get_id`, defaults to the name of the struct
+    pub id: Option<syn::LitStr>,
+    pub name: syn::LitStr,
+    pub desc: syn::LitStr,
+    // the command every action starts with, see `crate::plugin::CommandPlugin::get_root`
+    pub root: Option<syn::LitStr>,
+    pub priority: Option<i32>,
+    // async fns of the module run by `Plugin::on_load`, `Plugin::on_shutdown` and `Plugin::on_reload`
+    pub on_load: Option<syn::Path>,
+    pub on_shutdown: Option<syn::Path>,
+    pub on_reload: Option<syn::Path>,
+    // the type of the `plugins.<id>` section, see `crate::plugin::ConfigSpec`
+    pub config: Option<syn::Type>,
+    pub roles: Vec<syn::Ident>,
+}
+
+impl Parse for PluginMeta {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut id = None;
+        let mut name = None;
+        let mut desc = None;
+        let mut root = None;
+        let mut priority = None;
+        let mut on_load = None;
+        let mut on_shutdown = None;
+        let mut on_reload = None;
+        let mut config = None;
+        let mut roles = Vec::new();
+        while !input.is_empty() {
+            let key = input.parse::<syn::Ident>()?;
+            if ROLES.contains(&key.to_string().as_str()) {
+                // a bare role like `scheduled`, the plugin implements its trait by hand
+                roles.push(key);
+                if input.is_empty() {
+                    break;
+                }
+                input.parse::<syn::Token![,]>()?;
+                continue;
+            }
+            input.parse::<syn::Token![=]>()?;
+            match key.to_string().as_str() {
+                "id" => {
+                    let value = input.parse::<syn::LitStr>()?;
+                    if value.value().is_empty() || value.value().contains(char::is_whitespace) {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            r#"invalid id, expected a single word like "KeyWord""#,
+                        ));
+                    }
+                    id = Some(value);
+                }
+                "name" => name = Some(input.parse::<syn::LitStr>()?),
+                "desc" => desc = Some(input.parse::<syn::LitStr>()?),
+                "root" => {
+                    let value = input.parse::<syn::LitStr>()?;
+                    if value.value().trim().is_empty() || value.value().contains(char::is_whitespace) {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            r#"invalid root, expected a single command like "/keyword""#,
+                        ));
+                    }
+                    root = Some(value);
+                }
+                "priority" => {
+                    let negative = input.parse::<Option<syn::Token![-]>>()?.is_some();
+                    let value = input.parse::<syn::LitInt>()?.base10_parse::<i32>()?;
+                    priority = Some(if negative { -value } else { value });
+                }
+                "on_load" => on_load = Some(input.parse::<syn::Path>()?),
+                "on_shutdown" => on_shutdown = Some(input.parse::<syn::Path>()?),
+                "on_reload" => on_reload = Some(input.parse::<syn::Path>()?),
+                "config" => config = Some(input.parse::<syn::Type>()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!(r#"unknown option: "{}""#, key),
+                    ))
+                }
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<syn::Token![,]>()?;
+        }
+        let name = name.ok_or_else(|| input.error(r#"missing option: "name""#))?;
+        Ok(PluginMeta {
+            desc: desc.unwrap_or_else(|| syn::LitStr::new("", name.span())),
+            id,
+            name,
+            root,
+            priority,
+            on_load,
+            on_shutdown,
+            on_reload,
+            config,
+            roles,
+        })
+    }
+}
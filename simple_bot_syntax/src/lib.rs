@@ -0,0 +1,9 @@
+// the grammar of action patterns and rate limits, parsed by `simple_bot_macros` to check
+// them at compile time and by `simple_bot` to run them, so the two cannot disagree
+pub use pattern::{
+    parse_pattern, Constraint, OptionSpec, Placeholder, Segment, Syntax, SyntaxError,
+};
+pub use rate::{parse_rate, RateError};
+
+mod pattern;
+mod rate;
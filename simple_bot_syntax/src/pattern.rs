@@ -0,0 +1,376 @@
+use std::fmt::{Display, Formatter};
+
+use regex::Regex;
+
+// a pattern such as "/keyword add {chance:int} {keyword} {reply..} [--all]" taken apart,
+// the rules of matching it are described on `simple_bot::plugin::pattern::Pattern`
+pub struct Syntax {
+    // the pattern without its flags and options
+    pub segments: Vec<Segment>,
+    pub placeholders: Vec<Placeholder>,
+    pub options: Vec<OptionSpec>,
+}
+
+pub enum Segment {
+    Literal(String),
+    // index into the placeholders
+    Placeholder(usize),
+}
+
+pub struct Placeholder {
+    pub name: String,
+    // as written between the braces, like "chance:int"
+    pub spec: String,
+    pub constraint: Option<Constraint>,
+    pub greedy: bool,
+}
+
+pub struct OptionSpec {
+    // as written in the pattern, like "min-sim"
+    pub name: String,
+    // the type written in `[--name=<type>]`, None for a flag
+    pub value_type: Option<String>,
+}
+
+impl OptionSpec {
+    // the name of the parameter receiving it, `--min-sim` goes to `min_sim`
+    pub fn ident(&self) -> String {
+        self.name.replace('-', "_")
+    }
+}
+
+pub enum Constraint {
+    Int,
+    Uint,
+    Float,
+    Choices(Vec<String>),
+    // the regex as written in the pattern and the anchored one used for checking
+    Regex(String, Regex),
+}
+
+impl Constraint {
+    fn parse(name: &str, spec: &str) -> Result<Self, SyntaxError> {
+        Ok(match spec {
+            "int" => Constraint::Int,
+            "uint" => Constraint::Uint,
+            "float" => Constraint::Float,
+            _ if spec.len() > 1 && spec.starts_with('/') && spec.ends_with('/') => {
+                let source = &spec[1..spec.len() - 1];
+                let regex = Regex::new(&format!("^(?:{})$", source)).map_err(|e| {
+                    SyntaxError(format!(r#"invalid regex in placeholder "{}": {}"#, name, e))
+                })?;
+                Constraint::Regex(source.to_string(), regex)
+            }
+            _ if spec.split('|').any(|choice| choice.is_empty()) => {
+                return Err(SyntaxError(format!(
+                    r#"empty choice in placeholder "{}", expected choices like "{{{}:a|b}}""#,
+                    name, name
+                )))
+            }
+            _ => Constraint::Choices(spec.split('|').map(|s| s.to_string()).collect()),
+        })
+    }
+
+    pub fn check(&self, value: &str) -> bool {
+        match self {
+            Constraint::Int => value.parse::<i64>().is_ok(),
+            Constraint::Uint => value.parse::<u64>().is_ok(),
+            Constraint::Float => value.parse::<f64>().is_ok(),
+            Constraint::Choices(choices) => choices.iter().any(|c| c == value),
+            Constraint::Regex(_, regex) => regex.is_match(value),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Constraint::Int => "整数".to_string(),
+            Constraint::Uint => "非负整数".to_string(),
+            Constraint::Float => "小数".to_string(),
+            Constraint::Choices(choices) => format!("{} 之一", choices.join("|")),
+            Constraint::Regex(source, _) => format!("符合 /{}/ 的文本", source),
+        }
+    }
+
+    // the text a number takes, so that a message without one is not mistaken for the command
+    pub fn token(&self) -> Option<&'static str> {
+        match self {
+            Constraint::Int => Some(r"-?\d+"),
+            Constraint::Uint => Some(r"\d+"),
+            Constraint::Float => Some(r"-?(?:\d+(?:\.\d*)?|\.\d+)"),
+            _ => None,
+        }
+    }
+}
+
+// a mistake in a pattern, reported by the macros as a compile error
+#[derive(Debug)]
+pub struct SyntaxError(String);
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+pub fn parse_pattern(raw: &str) -> Result<Syntax, SyntaxError> {
+    let (positional, options) = split_options(raw)?;
+    let mut segments = Vec::new();
+    let mut placeholders: Vec<Placeholder> = Vec::new();
+    let mut rest = positional.as_str();
+    while let Some(start) = rest.find('{') {
+        if placeholders.last().is_some_and(|p| p.greedy) {
+            return Err(SyntaxError(
+                r#"a "{name..}" placeholder must be the last one"#.to_string(),
+            ));
+        }
+        segments.push(Segment::Literal(rest[..start].to_string()));
+        let end = closing_brace(&rest[start..])
+            .map(|end| start + end)
+            .ok_or_else(|| SyntaxError(r#"unclosed placeholder, missing "}""#.to_string()))?;
+        let placeholder = parse_placeholder(&rest[start + 1..end])?;
+        let duplicate = placeholders.iter().any(|p| p.name == placeholder.name)
+            || options.iter().any(|o| o.ident() == placeholder.name);
+        if duplicate {
+            return Err(SyntaxError(format!(
+                r#"duplicate placeholders found: "{}""#,
+                placeholder.name
+            )));
+        }
+        segments.push(Segment::Placeholder(placeholders.len()));
+        placeholders.push(placeholder);
+        rest = &rest[end + 1..];
+    }
+    if placeholders.last().is_some_and(|p| p.greedy) && !rest.trim().is_empty() {
+        return Err(SyntaxError(
+            r#"a "{name..}" placeholder must be at the end of the pattern"#.to_string(),
+        ));
+    }
+    segments.push(Segment::Literal(rest.to_string()));
+    Ok(Syntax {
+        segments,
+        placeholders,
+        options,
+    })
+}
+
+// take the `[--flag]` and `[--option=<type>]` sections out of a pattern
+fn split_options(raw: &str) -> Result<(String, Vec<OptionSpec>), SyntaxError> {
+    let mut positional = String::new();
+    let mut options: Vec<OptionSpec> = Vec::new();
+    let mut rest = raw;
+    while let Some(start) = rest.find("[--") {
+        positional.push_str(rest[..start].trim_end());
+        let end = rest[start..]
+            .find(']')
+            .map(|end| start + end)
+            .ok_or_else(|| SyntaxError(r#"unclosed option, missing "]""#.to_string()))?;
+        let spec = &rest[start + 3..end];
+        let (name, value_type) = match spec.split_once('=') {
+            Some((name, value_type)) => match value_type
+                .strip_prefix('<')
+                .and_then(|value_type| value_type.strip_suffix('>'))
+                .filter(|value_type| !value_type.is_empty())
+            {
+                Some(value_type) => (name, Some(value_type.to_string())),
+                None => {
+                    return Err(SyntaxError(format!(
+                        r#"invalid option: "[--{}]", expected "[--name=<type>]""#,
+                        spec
+                    )))
+                }
+            },
+            None => (spec, None),
+        };
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(SyntaxError(format!(r#"invalid option: "[--{}]""#, spec)));
+        }
+        let ident = name.replace('-', "_");
+        if options.iter().any(|o| o.ident() == ident) {
+            return Err(SyntaxError(format!(
+                r#"duplicate options found: "--{}""#,
+                name
+            )));
+        }
+        options.push(OptionSpec {
+            name: name.to_string(),
+            value_type,
+        });
+        rest = &rest[end + 1..];
+    }
+    positional.push_str(rest);
+    Ok((positional, options))
+}
+
+fn parse_placeholder(spec: &str) -> Result<Placeholder, SyntaxError> {
+    let (name, constraint) = match spec.split_once(':') {
+        Some((name, constraint)) => (name, Some(constraint)),
+        None => (spec, None),
+    };
+    let (name, greedy) = match name.strip_suffix("..") {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(SyntaxError(format!(
+            r#"invalid placeholder: "{{{}}}""#,
+            spec
+        )));
+    }
+    let constraint = match constraint {
+        Some(constraint) => Some(Constraint::parse(name, constraint)?),
+        None => None,
+    };
+    Ok(Placeholder {
+        name: name.to_string(),
+        spec: spec.to_string(),
+        constraint,
+        greedy,
+    })
+}
+
+// index of the `}` closing the placeholder at the start of `s`, braces of a regex
+// constraint like `{code:/\d{6}/}` are nested inside
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(raw: &str) -> String {
+        match parse_pattern(raw) {
+            Ok(_) => panic!("{} should not parse", raw),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn literal(segment: &Segment) -> &str {
+        match segment {
+            Segment::Literal(literal) => literal,
+            Segment::Placeholder(_) => panic!("expected a literal"),
+        }
+    }
+
+    #[test]
+    fn literals_and_placeholders() {
+        let syntax = parse_pattern("/keyword add {chance:int} {keyword} {reply..}").unwrap();
+        let names = syntax
+            .placeholders
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["chance", "keyword", "reply"]);
+        assert_eq!(syntax.placeholders[0].spec, "chance:int");
+        assert!(matches!(
+            syntax.placeholders[0].constraint,
+            Some(Constraint::Int)
+        ));
+        assert!(syntax.placeholders[1].constraint.is_none());
+        assert!(!syntax.placeholders[1].greedy);
+        assert!(syntax.placeholders[2].greedy);
+        assert_eq!(syntax.segments.len(), 7);
+        assert_eq!(literal(&syntax.segments[0]), "/keyword add ");
+        assert!(matches!(syntax.segments[1], Segment::Placeholder(0)));
+        assert_eq!(literal(&syntax.segments[2]), " ");
+        assert!(matches!(syntax.segments[5], Segment::Placeholder(2)));
+        assert_eq!(literal(&syntax.segments[6]), "");
+    }
+
+    #[test]
+    fn options_and_flags() {
+        let syntax = parse_pattern("/search {image} [--all] [--min-sim=<f64>]").unwrap();
+        assert_eq!(syntax.placeholders.len(), 1);
+        assert_eq!(syntax.options[0].name, "all");
+        assert!(syntax.options[0].value_type.is_none());
+        assert_eq!(syntax.options[1].name, "min-sim");
+        assert_eq!(syntax.options[1].ident(), "min_sim");
+        assert_eq!(syntax.options[1].value_type.as_deref(), Some("f64"));
+        // the options are taken out of the positional part
+        assert_eq!(literal(syntax.segments.last().unwrap()), "");
+    }
+
+    #[test]
+    fn constraints() {
+        let syntax = parse_pattern("{a:uint} {b:float} {mode:on|off} {code:/\\d{6}/}").unwrap();
+        let constraints = syntax
+            .placeholders
+            .iter()
+            .map(|p| p.constraint.as_ref().unwrap())
+            .collect::<Vec<_>>();
+        assert!(constraints[0].check("3") && !constraints[0].check("-3"));
+        assert_eq!(constraints[0].token(), Some(r"\d+"));
+        assert!(constraints[1].check("-0.5") && !constraints[1].check("x"));
+        assert!(constraints[2].check("off") && !constraints[2].check("of"));
+        assert_eq!(constraints[2].describe(), "on|off 之一");
+        assert_eq!(constraints[2].token(), None);
+        // the regex must match the whole value
+        assert!(constraints[3].check("123456"));
+        assert!(!constraints[3].check("1234567"));
+        assert_eq!(constraints[3].describe(), r"符合 /\d{6}/ 的文本");
+    }
+
+    #[test]
+    fn placeholder_errors() {
+        assert_eq!(error("{a} {a}"), r#"duplicate placeholders found: "a""#);
+        assert_eq!(
+            error("{all} [--all]"),
+            r#"duplicate placeholders found: "all""#
+        );
+        assert_eq!(error("/say {text"), r#"unclosed placeholder, missing "}""#);
+        assert_eq!(error("{a b}"), r#"invalid placeholder: "{a b}""#);
+        assert_eq!(error("{}"), r#"invalid placeholder: "{}""#);
+        assert_eq!(
+            error("{text..} {more}"),
+            r#"a "{name..}" placeholder must be the last one"#
+        );
+        assert_eq!(
+            error("{text..} end"),
+            r#"a "{name..}" placeholder must be at the end of the pattern"#
+        );
+        assert!(error("{mode:on|}").starts_with(r#"empty choice in placeholder "mode""#));
+        assert!(error("{code:/(/}").starts_with(r#"invalid regex in placeholder "code""#));
+    }
+
+    #[test]
+    fn option_errors() {
+        assert_eq!(error("/search [--all"), r#"unclosed option, missing "]""#);
+        assert_eq!(
+            error("/search [--min=f64]"),
+            r#"invalid option: "[--min=f64]", expected "[--name=<type>]""#
+        );
+        assert_eq!(
+            error("/search [--min=<>]").split(',').next(),
+            Some(r#"invalid option: "[--min=<>]""#)
+        );
+        assert_eq!(error("/search [--a b]"), r#"invalid option: "[--a b]""#);
+        assert_eq!(error("/search [--]"), r#"invalid option: "[--]""#);
+        assert_eq!(
+            error("/search [--all] [--all]"),
+            r#"duplicate options found: "--all""#
+        );
+        assert_eq!(
+            error("/search [--min-sim] [--min_sim]"),
+            r#"duplicate options found: "--min_sim""#
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum RateError {
+    // not "count/period", holding the whole text
+    Format(String),
+    Unit(String),
+    // a count or a period of 0, holding the whole text
+    Zero(String),
+}
+
+impl Display for RateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RateError::Format(s) => write!(f, "频率格式应为 次数/时间，如 1/30s: {}", s),
+            RateError::Unit(unit) => write!(f, "未知的时间单位: {}", unit),
+            RateError::Zero(s) => write!(f, "次数和时间都必须大于0: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for RateError {}
+
+// "count/period" like "1/30s", "5/1m" or "10/1h", a period without a unit is in seconds
+pub fn parse_rate(s: &str) -> Result<(usize, Duration), RateError> {
+    let format_error = || RateError::Format(s.to_string());
+    let (count, period) = s.split_once('/').ok_or_else(format_error)?;
+    let count = count.trim().parse::<usize>().map_err(|_| format_error())?;
+    let period = period.trim();
+    let (number, unit) = match period.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => period.split_at(i),
+        None => (period, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        _ => return Err(RateError::Unit(unit.to_string())),
+    };
+    let secs = number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(format_error)?;
+    if count == 0 || secs == 0 {
+        return Err(RateError::Zero(s.to_string()));
+    }
+    Ok((count, Duration::from_secs(secs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        assert_eq!(parse_rate("1/30s").unwrap(), (1, Duration::from_secs(30)));
+        assert_eq!(parse_rate("5/1m").unwrap(), (5, Duration::from_secs(60)));
+        assert_eq!(
+            parse_rate("10/1h").unwrap(),
+            (10, Duration::from_secs(3600))
+        );
+        assert_eq!(
+            parse_rate(" 3 / 20 ").unwrap(),
+            (3, Duration::from_secs(20))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(parse_rate("30s"), Err(RateError::Format(s)) if s == "30s"));
+        assert!(matches!(parse_rate("x/30s"), Err(RateError::Format(_))));
+        assert!(matches!(parse_rate("1/s"), Err(RateError::Format(_))));
+        assert!(matches!(
+            parse_rate("1/99999999999999999999h"),
+            Err(RateError::Format(_))
+        ));
+        assert!(matches!(
+            parse_rate("1/18446744073709551615h"),
+            Err(RateError::Format(_))
+        ));
+        assert!(matches!(parse_rate("1/1d"), Err(RateError::Unit(unit)) if unit == "d"));
+        assert!(matches!(parse_rate("0/30s"), Err(RateError::Zero(_))));
+        assert!(matches!(parse_rate("1/0m"), Err(RateError::Zero(_))));
+    }
+}